@@ -0,0 +1,345 @@
+//! Benchmarks for loading the symbols of a binary with a large symbol table, like a static
+//! python built with debug symbols.
+//!
+//! The binary is generated on the fly, so that the benchmarks don't need a C toolchain or a
+//! large checked in fixture. `hashmap_baseline` loads the same symbols the way spytools used
+//! to, with a `String` and a `HashMap` entry per symbol, for comparison. The heap memory that
+//! each approach keeps around for the symbols is printed before the benchmarks run.
+//!
+//! A real binary can be benchmarked as well, by pointing `SPYTOOLS_BENCH_BINARY` at it. A
+//! static python can be linked from the `python.o` and `libpython3.X.a` in the `config-3.X-*`
+//! directory of a python install:
+//!
+//! ```sh
+//! gcc -o python-static $CONFIG/python.o -Wl,--whole-archive $CONFIG/libpython3.13.a \
+//!     -Wl,--no-whole-archive -Xlinker -export-dynamic -ldl -lm -lpthread -lutil
+//! SPYTOOLS_BENCH_BINARY=python-static cargo bench --bench symbols
+//! ```
+//!
+//! Results on a single core Xeon VM, before (efcf19d) and after symbol names were kept in the
+//! mapped file and indexed lazily. Memory is the heap kept by `parse_binary`, with the lazy
+//! name index built by the first lookup in brackets. The python is 3.13.0 linked as above,
+//! with 9508 symbols:
+//!
+//! | binary            | parse_binary before | parse_binary after | memory before | memory after        |
+//! |-------------------|---------------------|--------------------|---------------|---------------------|
+//! | generated, 200k   | 153.8 ms            | 32.5 ms            | 22996 KiB     | 10240 (+5120) KiB   |
+//! | python 3.13       | 5.54 ms             | 1.80 ms            | 1010 KiB      | 644 (+320) KiB      |
+//!
+//! A single name lookup once the index is built went from 50.5 ns to 28.3 ns on the generated
+//! binary, and from 32.3 ns to 80.5 ns on the python.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use spytools::binary_parser::{parse_binary, Symbol, SymbolOrigin, Target};
+
+const SYMBOL_COUNT: usize = 200_000;
+
+fn symbol_name(index: usize) -> String {
+    format!(
+        "bench_module_{}_function_with_a_long_name_{}",
+        index % 97,
+        index
+    )
+}
+
+/// Writes a 64 bit little endian ELF shared library with a code segment, a .bss section and a
+/// `.symtab` holding `SYMBOL_COUNT` functions.
+fn write_binary(path: &Path) {
+    fn u16le(out: &mut Vec<u8>, value: u16) {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    fn u32le(out: &mut Vec<u8>, value: u32) {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    fn u64le(out: &mut Vec<u8>, value: u64) {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let text_offset = 0x1000u64;
+    let text_size = 0x1000u64;
+
+    let mut strtab = vec![0u8];
+    let mut symtab = vec![0u8; 24];
+    for index in 0..SYMBOL_COUNT {
+        let name = strtab.len() as u32;
+        strtab.extend_from_slice(symbol_name(index).as_bytes());
+        strtab.push(0);
+        u32le(&mut symtab, name);
+        symtab.push(0x12); // STB_GLOBAL, STT_FUNC
+        symtab.push(0);
+        u16le(&mut symtab, 1); // .text
+        u64le(&mut symtab, text_offset + (index as u64 * 16) % text_size);
+        u64le(&mut symtab, 16);
+    }
+    let shstrtab = b"\0.text\0.bss\0.symtab\0.strtab\0.shstrtab\0";
+
+    let symtab_offset = text_offset + text_size;
+    let strtab_offset = symtab_offset + symtab.len() as u64;
+    let shstrtab_offset = strtab_offset + strtab.len() as u64;
+    let section_headers_offset = (shstrtab_offset + shstrtab.len() as u64 + 7) & !7;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+    u16le(&mut out, 3); // ET_DYN
+    u16le(&mut out, 62); // EM_X86_64
+    u32le(&mut out, 1);
+    u64le(&mut out, 0); // e_entry
+    u64le(&mut out, 64); // e_phoff
+    u64le(&mut out, section_headers_offset);
+    u32le(&mut out, 0);
+    u16le(&mut out, 64);
+    u16le(&mut out, 56);
+    u16le(&mut out, 1);
+    u16le(&mut out, 64);
+    u16le(&mut out, 6);
+    u16le(&mut out, 5); // e_shstrndx
+
+    // PT_LOAD for the code
+    u32le(&mut out, 1);
+    u32le(&mut out, 5); // PF_R | PF_X
+    u64le(&mut out, text_offset);
+    u64le(&mut out, text_offset);
+    u64le(&mut out, text_offset);
+    u64le(&mut out, text_size);
+    u64le(&mut out, text_size);
+    u64le(&mut out, 0x1000);
+
+    out.resize(text_offset as usize + text_size as usize, 0);
+    out.extend_from_slice(&symtab);
+    out.extend_from_slice(&strtab);
+    out.extend_from_slice(shstrtab);
+    out.resize(section_headers_offset as usize, 0);
+
+    #[allow(clippy::too_many_arguments)]
+    fn section(
+        out: &mut Vec<u8>,
+        name: u32,
+        kind: u32,
+        flags: u64,
+        addr: u64,
+        offset: u64,
+        size: u64,
+        link: u32,
+        entsize: u64,
+    ) {
+        u32le(out, name);
+        u32le(out, kind);
+        u64le(out, flags);
+        u64le(out, addr);
+        u64le(out, offset);
+        u64le(out, size);
+        u32le(out, link);
+        u32le(out, if kind == 2 { 1 } else { 0 }); // sh_info: first global symbol
+        u64le(out, 8);
+        u64le(out, entsize);
+    }
+    section(&mut out, 0, 0, 0, 0, 0, 0, 0, 0);
+    section(&mut out, 1, 1, 6, text_offset, text_offset, text_size, 0, 0);
+    section(&mut out, 7, 8, 3, 0x3000, 0x3000, 0x100, 0, 0);
+    section(
+        &mut out,
+        12,
+        2,
+        0,
+        0,
+        symtab_offset,
+        symtab.len() as u64,
+        4,
+        24,
+    );
+    section(
+        &mut out,
+        20,
+        3,
+        0,
+        0,
+        strtab_offset,
+        strtab.len() as u64,
+        0,
+        0,
+    );
+    section(
+        &mut out,
+        28,
+        3,
+        0,
+        0,
+        shstrtab_offset,
+        shstrtab.len() as u64,
+        0,
+        0,
+    );
+
+    std::fs::write(path, out).unwrap();
+}
+
+fn binary_path() -> PathBuf {
+    let path = std::env::temp_dir().join(format!("spytools-bench-{}.elf", std::process::id()));
+    if !path.exists() {
+        write_binary(&path);
+    }
+    path
+}
+
+// counts the bytes allocated on the heap, to measure what the symbol tables keep around
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// returns the value made by `f`, along with how many bytes of heap it holds on to
+fn retained<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = f();
+    let after = ALLOCATED.load(Ordering::Relaxed);
+    (value, after.saturating_sub(before))
+}
+
+// a binary to benchmark, along with where its executable map is placed
+struct Binary {
+    path: PathBuf,
+    addr: u64,
+    size: u64,
+    file_offset: u64,
+    // a symbol from the middle of the symbol table
+    lookup: String,
+}
+
+impl Binary {
+    fn new(path: PathBuf) -> Binary {
+        let buffer = std::fs::read(&path).unwrap();
+        let elf = goblin::elf::Elf::parse(&buffer).unwrap();
+        let text = elf
+            .program_headers
+            .iter()
+            .find(|header| {
+                header.p_type == goblin::elf::program_header::PT_LOAD && header.is_executable()
+            })
+            .unwrap();
+        let base = if elf.header.e_type == goblin::elf::header::ET_EXEC {
+            0
+        } else {
+            0x7f00_0000_0000
+        };
+        let (syms, strtab) = if elf.syms.is_empty() {
+            (&elf.dynsyms, &elf.dynstrtab)
+        } else {
+            (&elf.syms, &elf.strtab)
+        };
+        let lookup = syms
+            .iter()
+            .skip(syms.len() / 2)
+            .filter(|sym| sym.st_shndx != 0)
+            .find_map(|sym| strtab.get_at(sym.st_name).filter(|name| !name.is_empty()))
+            .unwrap()
+            .to_owned();
+        Binary {
+            addr: base + (text.p_vaddr & !0xfff),
+            size: text.p_memsz,
+            file_offset: text.p_offset & !0xfff,
+            lookup,
+            path,
+        }
+    }
+
+    fn parse(&self) -> spytools::binary_parser::BinaryInfo {
+        parse_binary(
+            &Target::host(),
+            &self.path,
+            self.addr,
+            self.size,
+            Some(self.file_offset),
+            false,
+        )
+        .unwrap()
+    }
+
+    // loads the symbols like spytools used to, into a map owning a copy of every name
+    fn hashmap_baseline(&self) -> HashMap<String, Symbol> {
+        let file = std::fs::File::open(&self.path).unwrap();
+        let buffer = unsafe { memmap2::Mmap::map(&file).unwrap() };
+        let elf = goblin::elf::Elf::parse(&buffer).unwrap();
+        let mut symbols = HashMap::new();
+        for (syms, strtab) in [(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)] {
+            for sym in syms.iter() {
+                if let Some(name) = strtab.get_at(sym.st_name) {
+                    symbols.insert(
+                        name.to_string(),
+                        Symbol::new(sym.st_value + self.addr, SymbolOrigin::Symtab),
+                    );
+                }
+            }
+        }
+        symbols
+    }
+}
+
+fn bench_binary(c: &mut Criterion, name: &str, binary: &Binary) {
+    // the name index is built on the first lookup
+    let (info, parsed) = retained(|| binary.parse());
+    let (_, indexed) = retained(|| info.symbols.address(&binary.lookup));
+    let (symbols, baseline) = retained(|| binary.hashmap_baseline());
+    println!(
+        "{}: {} symbols, parse_binary keeps {} KiB ({} KiB more once looked up), \
+         hashmap_baseline keeps {} KiB",
+        name,
+        info.symbols.len(),
+        parsed / 1024,
+        indexed / 1024,
+        baseline / 1024
+    );
+    drop(symbols);
+
+    let lookup = &binary.lookup;
+    c.bench_function(&format!("{}/parse_binary", name), |b| {
+        b.iter(|| black_box(binary.parse()))
+    });
+    c.bench_function(&format!("{}/parse_binary_and_lookup", name), |b| {
+        b.iter(|| {
+            let info = binary.parse();
+            black_box(info.symbols.address(lookup).unwrap())
+        })
+    });
+    c.bench_function(&format!("{}/hashmap_baseline", name), |b| {
+        b.iter(|| black_box(binary.hashmap_baseline()[lookup].address))
+    });
+    c.bench_function(&format!("{}/lookup", name), |b| {
+        b.iter(|| black_box(info.symbols.address(black_box(lookup))))
+    });
+}
+
+fn bench_symbols(c: &mut Criterion) {
+    let path = binary_path();
+    let generated = Binary {
+        lookup: symbol_name(SYMBOL_COUNT / 2),
+        ..Binary::new(path.clone())
+    };
+    bench_binary(c, "generated", &generated);
+    std::fs::remove_file(&path).ok();
+
+    if let Some(path) = std::env::var_os("SPYTOOLS_BENCH_BINARY") {
+        bench_binary(c, "real", &Binary::new(PathBuf::from(path)));
+    }
+}
+
+criterion_group!(benches, bench_symbols);
+criterion_main!(benches);
@@ -0,0 +1,203 @@
+use goblin::elf::header::{
+    EM_386, EM_AARCH64, EM_ARM, EM_PPC, EM_PPC64, EM_RISCV, EM_S390, EM_X86_64,
+};
+use goblin::mach::cputype::{
+    CpuSubType, CpuType, CPU_SUBTYPE_ARM64_E, CPU_SUBTYPE_MASK, CPU_TYPE_ARM, CPU_TYPE_ARM64,
+    CPU_TYPE_POWERPC, CPU_TYPE_POWERPC64, CPU_TYPE_X86, CPU_TYPE_X86_64,
+};
+use goblin::pe::header::{
+    COFF_MACHINE_ARM, COFF_MACHINE_ARM64, COFF_MACHINE_ARMNT, COFF_MACHINE_X86, COFF_MACHINE_X86_64,
+};
+
+/// The CPU architecture a binary was built for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Architecture {
+    /// 32 bit x86 (i386)
+    X86,
+    /// x86_64 (amd64)
+    X86_64,
+    /// 32 bit arm
+    Arm,
+    /// 64 bit arm (arm64)
+    Aarch64,
+    /// 64 bit arm with pointer authentication (arm64e), which Apple's system binaries use.
+    /// Fat Mach-O binaries can have both an arm64 and an arm64e slice.
+    Arm64e,
+    /// 32 bit PowerPC
+    PowerPc,
+    /// 64 bit PowerPC
+    PowerPc64,
+    /// RISC-V
+    RiscV,
+    /// IBM Z
+    S390x,
+    /// An architecture spytools doesn't know about, with the machine from the binary's header:
+    /// `e_machine` for ELF, `cputype` for Mach-O and the COFF machine for PE. This is 0 for
+    /// [`native`](Architecture::native).
+    Unknown(u32),
+}
+
+impl Architecture {
+    /// Returns the architecture spytools was compiled for
+    pub fn native() -> Architecture {
+        if cfg!(target_arch = "x86_64") {
+            Architecture::X86_64
+        } else if cfg!(target_arch = "x86") {
+            Architecture::X86
+        } else if cfg!(target_arch = "aarch64") {
+            Architecture::Aarch64
+        } else if cfg!(target_arch = "arm") {
+            Architecture::Arm
+        } else if cfg!(target_arch = "powerpc64") {
+            Architecture::PowerPc64
+        } else if cfg!(target_arch = "powerpc") {
+            Architecture::PowerPc
+        } else if cfg!(any(target_arch = "riscv64", target_arch = "riscv32")) {
+            Architecture::RiscV
+        } else if cfg!(target_arch = "s390x") {
+            Architecture::S390x
+        } else {
+            Architecture::Unknown(0)
+        }
+    }
+
+    /// Returns `true` if both are the same kind of CPU, like arm64 and arm64e, which only
+    /// differ in the instructions and ABI that binaries can use
+    pub fn is_compatible(self, other: Architecture) -> bool {
+        let family = |arch| match arch {
+            Architecture::Arm64e => Architecture::Aarch64,
+            arch => arch,
+        };
+        family(self) == family(other)
+    }
+
+    pub(crate) fn from_mach_cputype(cputype: CpuType, cpusubtype: CpuSubType) -> Architecture {
+        match cputype {
+            CPU_TYPE_X86 => Architecture::X86,
+            CPU_TYPE_X86_64 => Architecture::X86_64,
+            CPU_TYPE_ARM => Architecture::Arm,
+            // the high bits of the subtype hold capabilities, like the pointer authentication
+            // ABI version of arm64e
+            CPU_TYPE_ARM64 if cpusubtype & !CPU_SUBTYPE_MASK == CPU_SUBTYPE_ARM64_E => {
+                Architecture::Arm64e
+            }
+            CPU_TYPE_ARM64 => Architecture::Aarch64,
+            CPU_TYPE_POWERPC => Architecture::PowerPc,
+            CPU_TYPE_POWERPC64 => Architecture::PowerPc64,
+            _ => Architecture::Unknown(cputype),
+        }
+    }
+
+    pub(crate) fn from_elf_machine(machine: u16, is_64: bool) -> Architecture {
+        match machine {
+            EM_386 => Architecture::X86,
+            EM_X86_64 => Architecture::X86_64,
+            EM_ARM => Architecture::Arm,
+            EM_AARCH64 => Architecture::Aarch64,
+            EM_PPC => Architecture::PowerPc,
+            EM_PPC64 => Architecture::PowerPc64,
+            EM_RISCV => Architecture::RiscV,
+            EM_S390 if is_64 => Architecture::S390x,
+            _ => Architecture::Unknown(machine.into()),
+        }
+    }
+
+    pub(crate) fn from_coff_machine(machine: u16) -> Architecture {
+        match machine {
+            COFF_MACHINE_X86 => Architecture::X86,
+            COFF_MACHINE_X86_64 => Architecture::X86_64,
+            COFF_MACHINE_ARM | COFF_MACHINE_ARMNT => Architecture::Arm,
+            COFF_MACHINE_ARM64 => Architecture::Aarch64,
+            _ => Architecture::Unknown(machine.into()),
+        }
+    }
+}
+
+/// Whether a binary uses 32 or 64 bit addresses (the ELF class)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    /// 32 bit addresses and pointers
+    Bits32,
+    /// 64 bit addresses and pointers
+    Bits64,
+}
+
+impl Class {
+    /// Returns the size in bytes of a pointer in the binary
+    pub fn pointer_size(self) -> usize {
+        match self {
+            Class::Bits32 => 4,
+            Class::Bits64 => 8,
+        }
+    }
+
+    pub(crate) fn from_is_64(is_64: bool) -> Class {
+        if is_64 {
+            Class::Bits64
+        } else {
+            Class::Bits32
+        }
+    }
+}
+
+/// The byte order of a binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first
+    Big,
+}
+
+impl Endianness {
+    pub(crate) fn from_is_little(little_endian: bool) -> Endianness {
+        if little_endian {
+            Endianness::Little
+        } else {
+            Endianness::Big
+        }
+    }
+}
+
+impl std::fmt::Display for Architecture {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Architecture::X86 => "x86",
+            Architecture::X86_64 => "x86_64",
+            Architecture::Arm => "arm",
+            Architecture::Aarch64 => "aarch64",
+            Architecture::Arm64e => "arm64e",
+            Architecture::PowerPc => "powerpc",
+            Architecture::PowerPc64 => "powerpc64",
+            Architecture::RiscV => "riscv",
+            Architecture::S390x => "s390x",
+            Architecture::Unknown(machine) => return write!(f, "unknown ({:#x})", machine),
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_architecture_from_header() {
+        assert_eq!(
+            Architecture::from_mach_cputype(CPU_TYPE_ARM64, 0),
+            Architecture::Aarch64
+        );
+        // arm64e binaries set the pointer authentication ABI in the high bits of the subtype
+        assert_eq!(
+            Architecture::from_mach_cputype(CPU_TYPE_ARM64, 0x8000_0000 | CPU_SUBTYPE_ARM64_E),
+            Architecture::Arm64e
+        );
+        assert!(Architecture::Arm64e.is_compatible(Architecture::Aarch64));
+        assert!(!Architecture::Arm64e.is_compatible(Architecture::X86_64));
+
+        // the machine of architectures spytools doesn't know about is kept, to report it
+        let sparc = Architecture::from_elf_machine(goblin::elf::header::EM_SPARCV9, true);
+        assert_eq!(sparc, Architecture::Unknown(43));
+        assert_eq!(sparc.to_string(), "unknown (0x2b)");
+    }
+}
@@ -0,0 +1,319 @@
+use std::convert::TryInto;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use goblin::elf::Elf;
+use log::*;
+use memmap2::Mmap;
+
+use crate::binary_parser::section::elf_section_data;
+use crate::binary_parser::target::join_root;
+
+/// A separate debug file that has been located and mapped into memory
+pub(crate) struct DebugFile {
+    pub path: PathBuf,
+    pub buffer: Mmap,
+}
+
+/// Reads the `.gnu_debuglink` section, returning the debug filename and the expected CRC32
+pub(crate) fn debuglink(elf: &Elf, buffer: &[u8]) -> Option<(String, u32)> {
+    let data = elf_section_data(elf, buffer, ".gnu_debuglink")?.ok()?;
+
+    // The section holds a NUL terminated filename, padded to a 4 byte boundary,
+    // followed by the CRC32 of the debug file (in the byte order of the ELF file)
+    let name_len = data.iter().position(|&b| b == 0)?;
+    let name = std::str::from_utf8(&data[..name_len]).ok()?;
+    let crc_offset = (name_len + 4) & !3;
+    let crc_bytes: [u8; 4] = data.get(crc_offset..crc_offset + 4)?.try_into().ok()?;
+    let crc = if elf.little_endian {
+        u32::from_le_bytes(crc_bytes)
+    } else {
+        u32::from_be_bytes(crc_bytes)
+    };
+    Some((name.to_owned(), crc))
+}
+
+/// Reads the `NT_GNU_BUILD_ID` note, returning the build-id as a lowercase hex string
+pub(crate) fn build_id(elf: &Elf, buffer: &[u8]) -> Option<String> {
+    // prefer the PT_NOTE segments (which survive stripping section headers), but fall back
+    // to the note sections for debug files, where program headers can point at NOBITS data
+    let notes = elf
+        .iter_note_headers(buffer)
+        .into_iter()
+        .flatten()
+        .chain(elf.iter_note_sections(buffer, None).into_iter().flatten());
+    for note in notes {
+        let note = match note {
+            Ok(note) => note,
+            Err(_) => continue,
+        };
+        if note.n_type == goblin::elf::note::NT_GNU_BUILD_ID
+            && note.name == "GNU"
+            && !note.desc.is_empty()
+        {
+            return Some(note.desc.iter().map(|b| format!("{:02x}", b)).collect());
+        }
+    }
+    None
+}
+
+/// Decompresses the MiniDebugInfo (`.gnu_debugdata` section) of the binary, if it has one.
+///
+/// This is an xz compressed ELF file holding a reduced `.symtab` with the symbols that aren't
+/// exported through `.dynsym`, as produced by Fedora/RHEL packaging.
+pub(crate) fn mini_debuginfo(elf: &Elf, buffer: &[u8]) -> Option<Vec<u8>> {
+    let data = match elf_section_data(elf, buffer, ".gnu_debugdata")? {
+        Ok(data) => data,
+        Err(e) => {
+            warn!("Failed to read .gnu_debugdata section: {}", e);
+            return None;
+        }
+    };
+
+    match xz_decompress(&data, MAX_MINI_DEBUGINFO_SIZE) {
+        Ok(decompressed) => Some(decompressed),
+        Err(e) => {
+            warn!("Failed to decompress .gnu_debugdata section: {:?}", e);
+            None
+        }
+    }
+}
+
+// MiniDebugInfo only holds a reduced symbol table, which is a few MiB even for large binaries.
+// xz doesn't give the decompressed size up front, so this bounds how much a corrupt or
+// malicious section can make the decoder produce.
+const MAX_MINI_DEBUGINFO_SIZE: usize = 256 << 20;
+
+// decompresses xz data, failing once it decompresses to more than `limit` bytes
+fn xz_decompress(data: &[u8], limit: usize) -> lzma_rs::error::Result<Vec<u8>> {
+    let mut decompressed = LimitedWriter {
+        data: Vec::new(),
+        limit,
+    };
+    lzma_rs::xz_decompress(&mut std::io::BufReader::new(data), &mut decompressed)?;
+    Ok(decompressed.data)
+}
+
+struct LimitedWriter {
+    data: Vec<u8>,
+    limit: usize,
+}
+
+impl std::io::Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.data.len() + buf.len() > self.limit {
+            return Err(std::io::Error::other(format!(
+                "decompressed data is larger than {} bytes",
+                self.limit
+            )));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Searches for the debug file for the given build-id in the `.build-id` directories
+/// under `/usr/lib/debug` (inside of `root`, and then on the host) and `search_paths`.
+/// Candidates whose own build-id doesn't match are skipped.
+pub(crate) fn find_build_id_file(
+    root: &Path,
+    search_paths: &[PathBuf],
+    build_id: &str,
+) -> Option<DebugFile> {
+    if build_id.len() < 3 {
+        return None;
+    }
+    let relative = Path::new(".build-id")
+        .join(&build_id[..2])
+        .join(format!("{}.debug", &build_id[2..]));
+
+    let mut candidates = vec![root.join("usr/lib/debug").join(&relative)];
+    for search_path in search_paths {
+        candidates.push(search_path.join(&relative));
+    }
+    if root != Path::new("/") {
+        candidates.push(Path::new("/usr/lib/debug").join(&relative));
+    }
+
+    for candidate in candidates {
+        let debug_file = match open(&candidate) {
+            Some(debug_file) => debug_file,
+            None => continue,
+        };
+        // a debug file left behind by an upgrade would give the symbols of another build
+        let actual = Elf::parse(&debug_file.buffer)
+            .ok()
+            .and_then(|elf| self::build_id(&elf, &debug_file.buffer));
+        if actual.as_deref() != Some(build_id) {
+            warn!(
+                "Ignoring debug file {}: build-id mismatch (expected {}, got {})",
+                candidate.display(),
+                build_id,
+                actual.as_deref().unwrap_or("none")
+            );
+            continue;
+        }
+        info!("Found debug file {}", candidate.display());
+        return Some(debug_file);
+    }
+    debug!("Failed to find debug file for build-id {}", build_id);
+    None
+}
+
+/// Searches for the file referenced by a `.gnu_debuglink` section of the binary at `path`.
+///
+/// `root` is prepended to the default locations, so that debug files installed inside of a
+/// container can be found through `/proc/<pid>/root`, and `search_paths` are searched after
+/// them. Candidates whose CRC32 doesn't match `crc` are skipped.
+pub(crate) fn find_debuglink_file(
+    root: &Path,
+    search_paths: &[PathBuf],
+    path: &Path,
+    name: &str,
+    crc: u32,
+) -> Option<DebugFile> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+    let relative_dir = dir.strip_prefix("/").unwrap_or(dir);
+
+    let mut candidates = vec![
+        join_root(root, dir).join(name),
+        join_root(root, dir).join(".debug").join(name),
+        root.join("usr/lib/debug").join(relative_dir).join(name),
+    ];
+    for search_path in search_paths {
+        candidates.push(search_path.join(relative_dir).join(name));
+        candidates.push(search_path.join(name));
+    }
+    if root != Path::new("/") {
+        candidates.push(Path::new("/usr/lib/debug").join(relative_dir).join(name));
+    }
+
+    for candidate in candidates {
+        let debug_file = match open(&candidate) {
+            Some(debug_file) => debug_file,
+            None => continue,
+        };
+        let actual = crc32fast::hash(&debug_file.buffer);
+        if actual != crc {
+            warn!(
+                "Ignoring debug file {}: CRC mismatch (expected {:08x}, got {:08x})",
+                candidate.display(),
+                crc,
+                actual
+            );
+            continue;
+        }
+        info!("Found debug file {}", candidate.display());
+        return Some(debug_file);
+    }
+    debug!(
+        "Failed to find debug file '{}' for {}",
+        name,
+        path.display()
+    );
+    None
+}
+
+fn open(path: &Path) -> Option<DebugFile> {
+    if !path.is_file() {
+        return None;
+    }
+    let file = File::open(path).ok()?;
+    let buffer = unsafe { Mmap::map(&file).ok()? };
+    Some(DebugFile {
+        path: path.to_owned(),
+        buffer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_parser::tests::fixture;
+
+    #[test]
+    fn test_debuglink() {
+        let path = fixture("libfixture.stripped");
+        let buffer = std::fs::read(&path).unwrap();
+        let elf = Elf::parse(&buffer).unwrap();
+        let (name, crc) = debuglink(&elf, &buffer).unwrap();
+        assert_eq!(name, "libfixture.debug");
+
+        let debug_file = find_debuglink_file(Path::new("/"), &[], &path, &name, crc).unwrap();
+        assert_eq!(debug_file.path, fixture("libfixture.debug"));
+
+        // a debug file whose CRC doesn't match must not be used
+        assert!(find_debuglink_file(Path::new("/"), &[], &path, &name, crc ^ 1).is_none());
+    }
+
+    #[test]
+    fn test_build_id() {
+        let path = fixture("libfixture.stripped");
+        let buffer = std::fs::read(&path).unwrap();
+        let elf = Elf::parse(&buffer).unwrap();
+        let build_id = build_id(&elf, &buffer).unwrap();
+        assert_eq!(build_id, "6a42ee62f5455ca29dcdb5bc9aac278e24fa11d3");
+
+        // the debug file keeps the note, so the ids can be compared
+        let debug_buffer = std::fs::read(fixture("libfixture.debug")).unwrap();
+        let debug_elf = Elf::parse(&debug_buffer).unwrap();
+        assert_eq!(
+            super::build_id(&debug_elf, &debug_buffer).as_ref(),
+            Some(&build_id)
+        );
+
+        // lookups happen inside the process's root directory
+        let debug_file = find_build_id_file(&fixture("root"), &[], &build_id).unwrap();
+        assert_eq!(
+            debug_file.path,
+            fixture("root/usr/lib/debug/.build-id/6a/42ee62f5455ca29dcdb5bc9aac278e24fa11d3.debug")
+        );
+        assert!(find_build_id_file(&fixture("root"), &[], "0123456789").is_none());
+
+        // root/ has a stale debug file under this build-id, which must not be used
+        let stale = "00112233445566778899aabbccddeeff00112233";
+        assert!(find_build_id_file(&fixture("root"), &[], stale).is_none());
+
+        // and in the target's search paths
+        let search_paths = [fixture("root/usr/lib/debug")];
+        assert!(find_build_id_file(&fixture("missing-root"), &[], &build_id).is_none());
+        let debug_file =
+            find_build_id_file(&fixture("missing-root"), &search_paths, &build_id).unwrap();
+        assert_eq!(
+            debug_file.path,
+            fixture("root/usr/lib/debug/.build-id/6a/42ee62f5455ca29dcdb5bc9aac278e24fa11d3.debug")
+        );
+    }
+
+    #[test]
+    fn test_mini_debuginfo() {
+        let buffer = std::fs::read(fixture("libfixture.minidebug")).unwrap();
+        let elf = Elf::parse(&buffer).unwrap();
+        let decompressed = mini_debuginfo(&elf, &buffer).unwrap();
+        let mini_elf = Elf::parse(&decompressed).unwrap();
+        let names: Vec<_> = mini_elf
+            .syms
+            .iter()
+            .filter_map(|sym| mini_elf.strtab.get_at(sym.st_name))
+            .filter(|name| !name.is_empty())
+            .collect();
+        assert_eq!(names, vec!["fixture_static_counter"]);
+
+        let buffer = std::fs::read(fixture("libfixture.stripped")).unwrap();
+        let elf = Elf::parse(&buffer).unwrap();
+        assert!(mini_debuginfo(&elf, &buffer).is_none());
+
+        // decompression stops at the limit, rather than running for as long as the data lasts
+        let mut compressed = Vec::new();
+        lzma_rs::xz_compress(&mut &vec![0; 0x100000][..], &mut compressed).unwrap();
+        assert_eq!(
+            xz_decompress(&compressed, 0x100000).unwrap().len(),
+            0x100000
+        );
+        assert!(xz_decompress(&compressed, 0x1000).is_err());
+    }
+}
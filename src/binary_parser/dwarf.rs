@@ -0,0 +1,508 @@
+use anyhow::Error;
+use gimli::{constants, AttributeValue, DebuggingInformationEntry, Reader, UnitOffset, UnitRef};
+
+/// The memory layout of a struct (or union or class), as described by DWARF debug info
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    /// The name the struct was looked up by
+    pub name: String,
+    /// The size of the struct in bytes
+    pub size: u64,
+    /// The fields of the struct, in declaration order. The fields of anonymous structs and
+    /// unions nested inside of the struct are listed as if they were declared directly in it.
+    pub fields: Vec<FieldLayout>,
+}
+
+/// A field of a struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    /// The name of the field
+    pub name: String,
+    /// The offset of the field in bytes from the start of the struct. For bit fields, this is
+    /// the offset of the byte holding the first bit.
+    pub offset: u64,
+    /// The size of the field in bytes, if known (flexible array members don't have one)
+    pub size: Option<u64>,
+    /// The name of the field's type as written in C, like `int`, `struct _frame *` or
+    /// `PyObject *[8]`, if it could be described
+    pub type_name: Option<String>,
+}
+
+impl StructLayout {
+    /// Returns the named field, if the struct has it
+    pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Returns the offset of the named field, if the struct has it
+    pub fn offset_of(&self, name: &str) -> Option<u64> {
+        self.field(name).map(|field| field.offset)
+    }
+}
+
+// type chains (typedefs of pointers to arrays of ...) are never this deep, but corrupt
+// debug info could make them loop
+const MAX_TYPE_DEPTH: usize = 32;
+
+/// Finds the definition of a struct, union or class by name, or by the name of a typedef
+/// for one (like `PyThreadState`, which is a typedef for `struct _ts`).
+pub(crate) fn struct_layout<R: Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    name: &str,
+) -> Result<Option<StructLayout>, Error> {
+    // a typedef can be for a struct that is only declared in the compilation unit it is in,
+    // in which case the definition is looked for by its tag name in the other units
+    let mut tag_name = None;
+    if let Some(layout) = find_struct(dwarf, name, true, &mut tag_name)? {
+        return Ok(Some(layout));
+    }
+    match tag_name {
+        Some(tag_name) => Ok(
+            find_struct(dwarf, &tag_name, false, &mut None)?.map(|mut layout| {
+                layout.name = name.to_owned();
+                layout
+            }),
+        ),
+        None => Ok(None),
+    }
+}
+
+fn find_struct<R: Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    name: &str,
+    follow_typedefs: bool,
+    tag_name: &mut Option<String>,
+) -> Result<Option<StructLayout>, Error> {
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let unit = UnitRef::new(dwarf, &unit);
+        let mut entries = unit.entries();
+        while let Some(entry) = entries.next_dfs()? {
+            let is_struct = is_struct_tag(entry.tag());
+            let is_typedef = follow_typedefs && entry.tag() == constants::DW_TAG_typedef;
+            if !is_struct && !is_typedef {
+                continue;
+            }
+            if entry_name(unit, entry).as_deref() != Some(name) {
+                continue;
+            }
+
+            let offset = if is_struct {
+                entry.offset()
+            } else {
+                match strip_typedefs(unit, entry.offset())? {
+                    Some(offset) => offset,
+                    None => continue,
+                }
+            };
+            let definition = unit.entry(offset)?;
+            if !is_struct_tag(definition.tag()) {
+                continue;
+            }
+            if definition
+                .attr_value(constants::DW_AT_declaration)
+                .is_some()
+            {
+                if tag_name.is_none() {
+                    *tag_name = entry_name(unit, &definition);
+                }
+                continue;
+            }
+
+            let size = match definition
+                .attr_value(constants::DW_AT_byte_size)
+                .and_then(|value| value.udata_value())
+            {
+                Some(size) => size,
+                None => continue,
+            };
+            let mut fields = Vec::new();
+            read_fields(unit, offset, 0, &mut fields, 0)?;
+            return Ok(Some(StructLayout {
+                name: name.to_owned(),
+                size,
+                fields,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+fn read_fields<R: Reader>(
+    unit: UnitRef<R>,
+    offset: UnitOffset<R::Offset>,
+    base: u64,
+    fields: &mut Vec<FieldLayout>,
+    depth: usize,
+) -> Result<(), Error> {
+    if depth > MAX_TYPE_DEPTH {
+        return Ok(());
+    }
+    let mut tree = unit.entries_tree(Some(offset))?;
+    let root = tree.root()?;
+    let mut children = root.children();
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        if entry.tag() != constants::DW_TAG_member {
+            continue;
+        }
+        let offset = base + member_offset(entry).unwrap_or(0);
+        let type_offset = match entry.attr_value(constants::DW_AT_type) {
+            Some(AttributeValue::UnitRef(offset)) => Some(offset),
+            _ => None,
+        };
+
+        match entry_name(unit, entry) {
+            Some(name) => fields.push(FieldLayout {
+                name,
+                offset,
+                size: match type_offset {
+                    Some(type_offset) => type_size(unit, type_offset, 0)?,
+                    None => None,
+                },
+                type_name: match type_offset {
+                    Some(type_offset) => type_name(unit, Some(type_offset), 0)?,
+                    None => None,
+                },
+            }),
+            None => {
+                // an anonymous struct or union, whose fields are accessed as if they were
+                // fields of the outer struct
+                if let Some(type_offset) = type_offset {
+                    if let Some(type_offset) = strip_typedefs(unit, type_offset)? {
+                        if is_struct_tag(unit.entry(type_offset)?.tag()) {
+                            read_fields(unit, type_offset, offset, fields, depth + 1)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the byte offset of a member in its struct. Union members don't have one.
+fn member_offset<R: Reader>(entry: &DebuggingInformationEntry<R>) -> Option<u64> {
+    match entry.attr_value(constants::DW_AT_data_member_location) {
+        Some(AttributeValue::Exprloc(expression)) => {
+            // DWARF 2 producers describe the offset as a DW_OP_plus_uconst expression
+            let mut reader = expression.0;
+            if reader.read_u8().ok()? == constants::DW_OP_plus_uconst.0 {
+                reader.read_uleb128().ok()
+            } else {
+                None
+            }
+        }
+        Some(value) => value.udata_value(),
+        None => entry
+            .attr_value(constants::DW_AT_data_bit_offset)
+            .and_then(|value| value.udata_value())
+            .map(|bits| bits / 8),
+    }
+}
+
+/// Follows typedefs and type qualifiers to the underlying type
+fn strip_typedefs<R: Reader>(
+    unit: UnitRef<R>,
+    mut offset: UnitOffset<R::Offset>,
+) -> Result<Option<UnitOffset<R::Offset>>, Error> {
+    for _ in 0..MAX_TYPE_DEPTH {
+        let entry = unit.entry(offset)?;
+        if !is_type_alias_tag(entry.tag()) {
+            return Ok(Some(offset));
+        }
+        offset = match entry.attr_value(constants::DW_AT_type) {
+            Some(AttributeValue::UnitRef(offset)) => offset,
+            _ => return Ok(None),
+        };
+    }
+    Ok(None)
+}
+
+fn type_size<R: Reader>(
+    unit: UnitRef<R>,
+    offset: UnitOffset<R::Offset>,
+    depth: usize,
+) -> Result<Option<u64>, Error> {
+    if depth > MAX_TYPE_DEPTH {
+        return Ok(None);
+    }
+    let entry = unit.entry(offset)?;
+    if let Some(size) = entry
+        .attr_value(constants::DW_AT_byte_size)
+        .and_then(|value| value.udata_value())
+    {
+        return Ok(Some(size));
+    }
+
+    let inner = match entry.attr_value(constants::DW_AT_type) {
+        Some(AttributeValue::UnitRef(offset)) => Some(offset),
+        _ => None,
+    };
+    match entry.tag() {
+        constants::DW_TAG_pointer_type
+        | constants::DW_TAG_reference_type
+        | constants::DW_TAG_rvalue_reference_type => {
+            Ok(Some(u64::from(unit.header.address_size())))
+        }
+        constants::DW_TAG_array_type => {
+            let element_size = match inner {
+                Some(inner) => type_size(unit, inner, depth + 1)?,
+                None => None,
+            };
+            Ok(match (element_size, array_count(unit, offset)?) {
+                (Some(element_size), Some(count)) => element_size.checked_mul(count),
+                _ => None,
+            })
+        }
+        tag if is_type_alias_tag(tag) => match inner {
+            Some(inner) => type_size(unit, inner, depth + 1),
+            None => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Returns the number of elements of an array type, multiplying the dimensions of
+/// multidimensional arrays. Returns `None` for arrays without a size, including flexible
+/// arrays that older versions of GCC give an upper bound of `u64::MAX`.
+fn array_count<R: Reader>(
+    unit: UnitRef<R>,
+    offset: UnitOffset<R::Offset>,
+) -> Result<Option<u64>, Error> {
+    let mut tree = unit.entries_tree(Some(offset))?;
+    let root = tree.root()?;
+    let mut children = root.children();
+    let mut total: Option<u64> = None;
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        if entry.tag() != constants::DW_TAG_subrange_type {
+            continue;
+        }
+        let count = match entry
+            .attr_value(constants::DW_AT_count)
+            .and_then(|value| value.udata_value())
+        {
+            Some(count) => count,
+            None => {
+                let upper = match entry
+                    .attr_value(constants::DW_AT_upper_bound)
+                    .and_then(|value| value.udata_value())
+                {
+                    Some(upper) => upper,
+                    None => return Ok(None),
+                };
+                let lower = entry
+                    .attr_value(constants::DW_AT_lower_bound)
+                    .and_then(|value| value.udata_value())
+                    .unwrap_or(0);
+                match upper.checked_add(1) {
+                    Some(end) => end.saturating_sub(lower),
+                    None => return Ok(None),
+                }
+            }
+        };
+        total = match total.unwrap_or(1).checked_mul(count) {
+            Some(total) => Some(total),
+            None => return Ok(None),
+        };
+    }
+    Ok(total)
+}
+
+fn type_name<R: Reader>(
+    unit: UnitRef<R>,
+    offset: Option<UnitOffset<R::Offset>>,
+    depth: usize,
+) -> Result<Option<String>, Error> {
+    // a missing type means void, which is only valid behind a pointer
+    let offset = match offset {
+        Some(offset) => offset,
+        None => return Ok(Some("void".to_owned())),
+    };
+    if depth > MAX_TYPE_DEPTH {
+        return Ok(None);
+    }
+    let entry = unit.entry(offset)?;
+    let inner = match entry.attr_value(constants::DW_AT_type) {
+        Some(AttributeValue::UnitRef(offset)) => Some(offset),
+        Some(_) => return Ok(None),
+        None => None,
+    };
+    let name = entry_name(unit, &entry);
+    Ok(match entry.tag() {
+        constants::DW_TAG_structure_type => name.map(|name| format!("struct {}", name)),
+        constants::DW_TAG_union_type => name.map(|name| format!("union {}", name)),
+        constants::DW_TAG_enumeration_type => name.map(|name| format!("enum {}", name)),
+        constants::DW_TAG_pointer_type => {
+            type_name(unit, inner, depth + 1)?.map(|inner| format!("{} *", inner))
+        }
+        constants::DW_TAG_const_type => {
+            type_name(unit, inner, depth + 1)?.map(|inner| format!("const {}", inner))
+        }
+        constants::DW_TAG_volatile_type => {
+            type_name(unit, inner, depth + 1)?.map(|inner| format!("volatile {}", inner))
+        }
+        constants::DW_TAG_array_type => {
+            let count = array_count(unit, offset)?;
+            type_name(unit, inner, depth + 1)?.map(|inner| match count {
+                Some(count) => format!("{}[{}]", inner, count),
+                None => format!("{}[]", inner),
+            })
+        }
+        constants::DW_TAG_subroutine_type => Some("function".to_owned()),
+        _ => name,
+    })
+}
+
+fn entry_name<R: Reader>(unit: UnitRef<R>, entry: &DebuggingInformationEntry<R>) -> Option<String> {
+    let value = entry.attr_value(constants::DW_AT_name)?;
+    let name = unit.attr_string(value).ok()?;
+    name.to_string_lossy().ok().map(|name| name.into_owned())
+}
+
+fn is_struct_tag(tag: constants::DwTag) -> bool {
+    matches!(
+        tag,
+        constants::DW_TAG_structure_type
+            | constants::DW_TAG_union_type
+            | constants::DW_TAG_class_type
+    )
+}
+
+fn is_type_alias_tag(tag: constants::DwTag) -> bool {
+    matches!(
+        tag,
+        constants::DW_TAG_typedef
+            | constants::DW_TAG_const_type
+            | constants::DW_TAG_volatile_type
+            | constants::DW_TAG_restrict_type
+            | constants::DW_TAG_atomic_type
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::binary_parser::tests::parse_fixture;
+
+    #[test]
+    fn test_struct_layout() {
+        let info = parse_fixture("libstructs.stripped");
+        // the layouts come from the (compressed) debug info in the separate debug file
+        assert!(info.debug_filename.is_some());
+
+        // looked up through a typedef, whose first appearance is for a declaration
+        let tstate = info.struct_layout("ThreadState").unwrap();
+        assert_eq!(tstate.name, "ThreadState");
+        assert_eq!(tstate.size, 72);
+        assert_eq!(
+            tstate,
+            info.struct_layout("_ts")
+                .map(|mut layout| {
+                    layout.name = "ThreadState".to_owned();
+                    layout
+                })
+                .unwrap()
+        );
+
+        let frame = tstate.field("frame").unwrap();
+        assert_eq!(frame.offset, 16);
+        assert_eq!(frame.size, Some(8));
+        assert_eq!(frame.type_name.as_deref(), Some("struct _frame *"));
+
+        let name = tstate.field("name").unwrap();
+        assert_eq!((name.offset, name.size), (28, Some(12)));
+        assert_eq!(name.type_name.as_deref(), Some("char[12]"));
+
+        // fields of the anonymous union are part of the struct
+        assert_eq!(tstate.offset_of("as_long"), Some(40));
+        assert_eq!(tstate.offset_of("as_double"), Some(40));
+        assert_eq!(
+            tstate.field("data").unwrap().type_name.as_deref(),
+            Some("const void *")
+        );
+        assert_eq!(tstate.offset_of("tracing"), Some(56));
+        assert_eq!(tstate.offset_of("thread_id"), Some(64));
+
+        // an anonymous struct, only reachable through its typedef
+        let interp = info.struct_layout("InterpreterState").unwrap();
+        assert_eq!(interp.size, 32);
+        let head = interp.field("tstate_head").unwrap();
+        assert_eq!(head.offset, 0);
+        assert_eq!(head.type_name.as_deref(), Some("ThreadState *"));
+        let table = interp.field("table").unwrap();
+        assert_eq!((table.offset, table.size), (12, Some(16)));
+
+        assert!(info.struct_layout("_frame").is_err());
+        assert!(info.struct_layout("missing").is_err());
+    }
+
+    #[test]
+    fn test_struct_layout_flexible_arrays() {
+        use gimli::{EndianSlice, LittleEndian, SectionId};
+
+        // struct flex { long n; long old[]; long new[]; }, where older versions of GCC give
+        // the subrange of old an upper bound of u64::MAX, and newer ones leave it out
+        // abbreviation code, tag, whether there are children, then the attributes and forms
+        let abbrev = [
+            // compile unit
+            &[1, 0x11, 1, 0, 0][..],
+            // struct: name, byte size
+            &[2, 0x13, 1, 0x03, 0x08, 0x0b, 0x0b, 0, 0],
+            // member: name, type, location
+            &[3, 0x0d, 0, 0x03, 0x08, 0x49, 0x13, 0x38, 0x0b, 0, 0],
+            // base type: name, byte size
+            &[4, 0x24, 0, 0x03, 0x08, 0x0b, 0x0b, 0, 0],
+            // array: type
+            &[5, 0x01, 1, 0x49, 0x13, 0, 0],
+            // subrange: upper bound (data8)
+            &[6, 0x21, 0, 0x2f, 0x07, 0, 0],
+            // subrange without bounds, then the end of the table
+            &[7, 0x21, 0, 0, 0, 0],
+        ]
+        .concat();
+        // unit length, version 4, abbrev offset and address size, then the compile unit
+        let mut info = vec![0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 8, 1];
+        let long = info.len() as u32;
+        info.extend_from_slice(b"\x04long\0\x08");
+        let old = info.len() as u32;
+        info.push(5);
+        info.extend_from_slice(&long.to_le_bytes());
+        info.push(6);
+        info.extend_from_slice(&u64::MAX.to_le_bytes());
+        info.push(0);
+        let new = info.len() as u32;
+        info.push(5);
+        info.extend_from_slice(&long.to_le_bytes());
+        info.extend_from_slice(&[7, 0]);
+        info.extend_from_slice(b"\x02flex\0\x08");
+        for (name, type_offset, location) in [("n", long, 0), ("old", old, 8), ("new", new, 8)] {
+            info.push(3);
+            info.extend_from_slice(name.as_bytes());
+            info.push(0);
+            info.extend_from_slice(&type_offset.to_le_bytes());
+            info.push(location);
+        }
+        info.extend_from_slice(&[0, 0]);
+        let length = info.len() as u32 - 4;
+        info[..4].copy_from_slice(&length.to_le_bytes());
+
+        let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+            let data = match id {
+                SectionId::DebugInfo => &info[..],
+                SectionId::DebugAbbrev => &abbrev[..],
+                _ => &[],
+            };
+            Ok(EndianSlice::new(data, LittleEndian))
+        })
+        .unwrap();
+        let flex = super::struct_layout(&dwarf, "flex").unwrap().unwrap();
+        assert_eq!(flex.size, 8);
+        for name in ["old", "new"] {
+            let field = flex.field(name).unwrap();
+            assert_eq!((field.offset, field.size), (8, None));
+            assert_eq!(field.type_name.as_deref(), Some("long[]"));
+        }
+    }
+}
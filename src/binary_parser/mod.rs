@@ -0,0 +1,1424 @@
+/// For describing the CPU architecture of a binary
+pub mod arch;
+/// For locating separate debug files and embedded MiniDebugInfo that hold the full symbol table
+pub mod debug_file;
+/// For reading type information from DWARF debug info
+pub mod dwarf;
+/// For describing the sections of a binary
+pub mod section;
+/// For describing the symbols of a binary
+pub mod symbol;
+/// For describing the process that binaries are parsed for
+pub mod target;
+/// For reading the USDT probes of a binary
+pub mod usdt;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{format_err, Error};
+use goblin;
+use goblin::Object;
+use log::*;
+use memmap2::Mmap;
+
+pub use arch::{Architecture, Class, Endianness};
+pub use dwarf::{FieldLayout, StructLayout};
+pub use section::{Section, SectionKind};
+pub use symbol::{
+    demangle, Symbol, SymbolBinding, SymbolKind, SymbolMap, SymbolOrigin, SymbolVisibility,
+};
+pub use target::{FileSource, FileStatus, Target};
+pub use usdt::UsdtProbe;
+
+/// Metadata for a program, including its symbols, BSS section, location in memory, etc.
+pub struct BinaryInfo {
+    /// The filesystem path to the binary
+    pub filename: std::path::PathBuf,
+    /// The CPU architecture of the binary. For fat Mach-O binaries, this is the architecture
+    /// of the slice that was parsed.
+    pub arch: Architecture,
+    /// Whether the binary uses 32 or 64 bit addresses, which is the size of pointers in the
+    /// process
+    pub class: Class,
+    /// The byte order of the binary
+    pub endianness: Endianness,
+    /// The symbols declared by the binary, indexed by name
+    pub symbols: SymbolMap,
+    /// The address of the BSS section
+    pub bss_addr: u64,
+    /// The size in bytes of the BSS section
+    pub bss_size: u64,
+    /// The same value as `load_bias`, kept for compatibility
+    pub offset: u64,
+    /// The difference between the addresses the binary was loaded at and the addresses
+    /// recorded in its headers and symbol tables (the virtual addresses in the file).
+    ///
+    /// Adding this to an address from the file gives the address in the process's memory.
+    /// This is 0 for non-PIE executables, and can wrap around (be "negative") for prelinked
+    /// libraries that were loaded below their preferred address.
+    pub load_bias: u64,
+    /// The start address of the memory map that the binary was parsed for
+    /// (usually the executable mapping)
+    pub addr: u64,
+    /// The size in bytes of the memory map that the binary was parsed for
+    pub size: u64,
+    /// The separate debug file that additional symbols were loaded from, if any
+    pub debug_filename: Option<PathBuf>,
+    /// The GNU build-id of the binary as a lowercase hex string, if it has one.
+    ///
+    /// Two binaries with the same build-id are the same build, which makes this a good key for
+    /// caching per-binary information.
+    pub build_id: Option<String>,
+    /// The sections of the binary, with addresses relocated to the process's memory
+    pub sections: Vec<Section>,
+    /// The thread local storage segment (`PT_TLS`) of the binary, if it has one
+    pub tls: Option<TlsInfo>,
+    /// The USDT probes of the binary, with addresses relocated to the process's memory
+    pub usdt_probes: Vec<UsdtProbe>,
+    /// Whether the file the binary was read from is the file that is mapped into the process.
+    /// Binaries whose files were replaced or deleted are read from the mapped file when
+    /// possible, so `filename` can be in `/proc/<pid>/map_files`.
+    pub file_status: FileStatus,
+    /// The name that other binaries link to this one by, for shared libraries (the ELF
+    /// `DT_SONAME`, the install name of a dylib or the name of a DLL)
+    pub soname: Option<String>,
+    /// The names of the shared libraries the binary depends on, in the order the dynamic linker
+    /// loads them (`DT_NEEDED` entries, dylib load commands or DLL imports)
+    pub needed: Vec<String>,
+    // the contents of the file, for reading sections on demand
+    buffer: Arc<Mmap>,
+    // the class and byte order for ELF files
+    elf_ctx: Option<goblin::container::Ctx>,
+    // the address and size of the dynamic segment (PT_DYNAMIC) in the process's memory, for
+    // ELF files that have one
+    elf_dynamic: Option<(u64, u64)>,
+    // the contents and sections of the separate debug file, if one was found
+    debug_buffer: Option<Arc<Mmap>>,
+    debug_sections: Vec<Section>,
+}
+
+/// The thread local storage segment of an ELF binary. Every thread gets its own block of
+/// `size` bytes, initialized from the image, and the values of TLS symbols are offsets into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// The address of the initialization image (`.tdata`) in the process's memory
+    pub image_addr: u64,
+    /// The size in bytes of the initialization image, the rest of the block is zero filled
+    pub image_size: u64,
+    /// The size in bytes of the TLS block
+    pub size: u64,
+    /// The required alignment of the TLS block
+    pub align: u64,
+    /// The TLS module id the dynamic linker assigned to the binary (the index into the dtv),
+    /// if known. The main executable is always module 1.
+    pub module_id: Option<u64>,
+}
+
+impl BinaryInfo {
+    /// Returns `true` if `addr` is inside of the process's memory space.
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.addr && addr < (self.addr + self.size)
+    }
+
+    /// Returns the first section with the given name (like `.data` or `__bss`), if any
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    pub(crate) fn is_elf(&self) -> bool {
+        self.elf_ctx.is_some()
+    }
+
+    // the address and size of the dynamic segment in the process's memory. Unlike the
+    // .dynamic section header, this is what the dynamic linker uses, and it can't be stripped.
+    pub(crate) fn elf_dynamic(&self) -> Option<(u64, u64)> {
+        self.elf_dynamic
+    }
+
+    /// Returns the contents of the named section as stored in the file, decompressing
+    /// sections with `SHF_COMPRESSED` set (zlib or zstd). Zero filled sections (like `.bss`)
+    /// aren't stored in the file, and are returned empty.
+    pub fn section_data(&self, name: &str) -> Result<Cow<'_, [u8]>, Error> {
+        let section = self.section(name).ok_or_else(|| {
+            format_err!(
+                "Failed to find section {} in {}",
+                name,
+                self.filename.display()
+            )
+        })?;
+        section::section_data(&self.buffer, section, self.elf_ctx)
+    }
+
+    /// Returns the layout of a struct from the DWARF debug info of the binary, looking it up by
+    /// its tag name (`_ts`) or by the name of a typedef for it (`PyThreadState`).
+    ///
+    /// The debug info is read from the separate debug file if one was found, and otherwise
+    /// from the binary itself. Every call searches through the debug info again, so callers
+    /// should hold on to the layouts they need.
+    pub fn struct_layout(&self, name: &str) -> Result<StructLayout, Error> {
+        let ctx = self.elf_ctx.ok_or_else(|| {
+            format_err!(
+                "Reading debug info isn't supported for {}",
+                self.filename.display()
+            )
+        })?;
+        let (buffer, sections) = match &self.debug_buffer {
+            Some(debug_buffer) => (&debug_buffer[..], &self.debug_sections),
+            None => (&self.buffer[..], &self.sections),
+        };
+        if !sections.iter().any(|section| section.name == ".debug_info") {
+            return Err(format_err!(
+                "Failed to find DWARF debug info for {}",
+                self.filename.display()
+            ));
+        }
+
+        let loaded = gimli::DwarfSections::load(|id| -> Result<Cow<[u8]>, Error> {
+            match sections.iter().find(|section| section.name == id.name()) {
+                Some(section) => section::section_data(buffer, section, Some(ctx)),
+                None => Ok(Cow::Borrowed(&[])),
+            }
+        })?;
+        let endian = if ctx.le.is_little() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+        let dwarf = loaded.borrow(|section| gimli::EndianSlice::new(section, endian));
+        dwarf::struct_layout(&dwarf, name)?.ok_or_else(|| {
+            format_err!(
+                "Failed to find struct {} in the debug info of {}",
+                name,
+                self.filename.display()
+            )
+        })
+    }
+
+    /// Returns the section containing `addr`, if any
+    pub fn section_containing(&self, addr: u64) -> Option<&Section> {
+        self.sections.iter().find(|section| section.contains(addr))
+    }
+
+    /// Returns the memory ranges of the writable data sections (`.data`, `.bss`, `.PyRuntime`,
+    /// the sections of the `__DATA` segment etc), with adjacent sections merged together.
+    ///
+    /// These are the ranges worth scanning when looking for interpreter state that isn't
+    /// reachable from a symbol.
+    pub fn writable_data_ranges(&self) -> Vec<Range<u64>> {
+        section::writable_data_ranges(&self.sections)
+    }
+
+    /// Returns the name of the function or object containing `addr`, along with the offset
+    /// of `addr` inside of it.
+    pub fn symbolize(&self, addr: u64) -> Option<(&str, u64)> {
+        self.symbols.symbolize(addr)
+    }
+}
+
+/// Parses a binary file, given its path as seen by the process described by `target`.
+///
+/// `addr` and `size` describe a memory map of the binary in the process, and `file_offset` is
+/// the offset in the file that the map starts at (like `MapRange::offset` on Linux). The file
+/// offset is needed to compute the load bias of ELF binaries reliably. When it isn't known,
+/// the map is assumed to hold the first executable segment (or the `__TEXT` segment of Mach-O
+/// binaries).
+pub fn parse_binary(
+    target: &Target,
+    filename: &Path,
+    addr: u64,
+    size: u64,
+    file_offset: Option<u64>,
+    is_bin: bool,
+) -> Result<BinaryInfo, Error> {
+    parse(
+        target,
+        filename,
+        addr,
+        size,
+        Placement::FileOffset(file_offset),
+        is_bin,
+    )
+}
+
+/// Parses a Mach-O binary whose segments weren't loaded next to each other, like the
+/// libraries in the dyld shared cache, given where the named segment (like `__DATA`) was
+/// loaded.
+///
+/// Symbols and sections are relocated by the position of that segment, so only the ones
+/// inside of it have the right addresses. The BSS is reported as the whole segment, since
+/// zero filled data can be split over several sections of it.
+pub fn parse_mach_segment(
+    target: &Target,
+    filename: &Path,
+    segment: &str,
+    addr: u64,
+    size: u64,
+) -> Result<BinaryInfo, Error> {
+    parse(
+        target,
+        filename,
+        addr,
+        size,
+        Placement::MachSegment(segment),
+        false,
+    )
+}
+
+// which part of the binary the memory map passed to `parse` holds
+enum Placement<'a> {
+    FileOffset(Option<u64>),
+    MachSegment(&'a str),
+}
+
+fn parse(
+    target: &Target,
+    filename: &Path,
+    addr: u64,
+    size: u64,
+    placement: Placement,
+    is_bin: bool,
+) -> Result<BinaryInfo, Error> {
+    let arch = target.arch;
+    let file_offset = match placement {
+        Placement::FileOffset(file_offset) => file_offset,
+        Placement::MachSegment(_) => None,
+    };
+
+    // separate debug files are searched for relative to the path of the binary as
+    // seen by the process, inside of the process's root directory
+    let path = target::strip_deleted(filename).0;
+    let root = target.root_dir();
+    let (filename, file_status) = target.mapped_file(filename, is_bin, addr, size);
+    let filename = &filename;
+
+    let mut symbols = SymbolMap::new();
+
+    // Read in the filename. The symbol names point into the file, so it stays mapped for as
+    // long as the symbols are around
+    let file = File::open(filename)?;
+    let buffer = Arc::new(unsafe { Mmap::map(&file)? });
+    let source = symbols.add_source(buffer.clone());
+
+    // Use goblin to parse the binary
+    match Object::parse(&buffer)? {
+        Object::Mach(mach) => {
+            // Get the mach binary from the archive, and where it starts in the file
+            let (mach, file_base) = match mach {
+                goblin::mach::Mach::Binary(mach) => (mach, 0),
+                goblin::mach::Mach::Fat(fat) => {
+                    let arches = fat.arches()?;
+                    let slice_arch = |fat_arch: &goblin::mach::fat::FatArch| {
+                        Architecture::from_mach_cputype(fat_arch.cputype(), fat_arch.cpusubtype())
+                    };
+                    // prefer the slice for the exact architecture, like arm64e over arm64 for
+                    // arm64e processes, but fall back to one the process can run
+                    let find_arch = |wanted: Architecture| {
+                        arches
+                            .iter()
+                            .find(|fat_arch| slice_arch(fat_arch) == wanted)
+                            .or_else(|| {
+                                arches
+                                    .iter()
+                                    .find(|fat_arch| slice_arch(fat_arch).is_compatible(wanted))
+                            })
+                    };
+                    let arch = match arch {
+                        Some(arch) => find_arch(arch).ok_or_else(|| {
+                            format_err!(
+                                "Failed to find {} arch in FAT archive in {} (found {})",
+                                arch,
+                                filename.display(),
+                                arches
+                                    .iter()
+                                    .map(|fat_arch| slice_arch(fat_arch).to_string())
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            )
+                        })?,
+                        None => find_arch(Architecture::native())
+                            .or_else(|| {
+                                let arch = arches.iter().find(|fat_arch| fat_arch.is_64())?;
+                                warn!(
+                                    "Failed to find {} arch in FAT archive in {}, using {}",
+                                    Architecture::native(),
+                                    filename.display(),
+                                    slice_arch(arch)
+                                );
+                                Some(arch)
+                            })
+                            .ok_or_else(|| {
+                                format_err!(
+                                    "Failed to find 64 bit arch in FAT archive in {}",
+                                    filename.display()
+                                )
+                            })?,
+                    };
+                    let bytes = &buffer[arch.offset as usize..][..arch.size as usize];
+                    (
+                        goblin::mach::MachO::parse(bytes, 0)?,
+                        u64::from(arch.offset),
+                    )
+                }
+            };
+
+            // Mach-O symbols hold the addresses of the segments in the file (executables place
+            // __TEXT, and _mh_execute_header, at 0x100000000), so the load bias is the distance
+            // from there to the address the mapped segment was loaded at
+            let segname =
+                |segment: &goblin::mach::segment::Segment| segment.name().unwrap_or("").to_owned();
+            let text_segment = || {
+                mach.segments
+                    .iter()
+                    .find(|segment| segment.fileoff == 0 && segment.filesize > 0)
+                    .or_else(|| mach.segments.iter().next())
+            };
+            let segment = match placement {
+                Placement::MachSegment(name) => mach
+                    .segments
+                    .iter()
+                    .find(|segment| segname(segment) == name),
+                Placement::FileOffset(Some(file_offset)) => mach
+                    .segments
+                    .iter()
+                    .find(|segment| {
+                        segment.filesize > 0 && segment.fileoff + file_base == file_offset
+                    })
+                    .or_else(text_segment),
+                Placement::FileOffset(None) => text_segment(),
+            }
+            .ok_or_else(|| {
+                format_err!(
+                    "Failed to find the segment for the map at {:#x} in {}",
+                    addr,
+                    filename.display()
+                )
+            })?;
+            let offset = addr.wrapping_sub(segment.vmaddr);
+            let map_segment = segname(segment);
+
+            let mut sections = Vec::new();
+            for segment in mach.segments.iter() {
+                for (section, _) in &segment.sections()? {
+                    let mut section = Section::from_mach(section, segment.initprot, offset)?;
+                    section.file_offset += file_base;
+                    sections.push(section);
+                }
+            }
+
+            // zero filled globals live in __bss, or in __common for tentative definitions
+            let bss = ["__bss", "__common"]
+                .iter()
+                .find_map(|name| sections.iter().find(|section| section.name == *name));
+            let (bss_addr, bss_size) = match placement {
+                Placement::MachSegment(_) => (addr, size),
+                Placement::FileOffset(_) => bss.map_or((0, 0), |bss| (bss.addr, bss.size)),
+            };
+            debug!(
+                "Relocated {} by {:#x} using the {} segment",
+                filename.display(),
+                offset,
+                map_segment
+            );
+
+            if let Some(syms) = mach.symbols {
+                for symbol in syms.iter() {
+                    let (name, value) = symbol?;
+                    // almost every symbol we care about starts with an extra _, remove to normalize
+                    // with the entries seen on linux/windows
+                    if name.starts_with('_') && !value.is_undefined() {
+                        symbols.insert_from_source(
+                            source,
+                            &name[1..],
+                            Symbol::from_mach(&value, &sections, offset),
+                            true,
+                        );
+                    }
+                }
+            }
+            symbol::infer_sizes(&mut symbols, &sections);
+            Ok(BinaryInfo {
+                filename: filename.to_owned(),
+                arch: Architecture::from_mach_cputype(mach.header.cputype, mach.header.cpusubtype),
+                class: Class::from_is_64(mach.is_64),
+                endianness: Endianness::from_is_little(mach.little_endian),
+                symbols,
+                bss_addr,
+                bss_size,
+                offset,
+                load_bias: offset,
+                addr,
+                size,
+                debug_filename: None,
+                build_id: None,
+                sections,
+                tls: None,
+                usdt_probes: Vec::new(),
+                file_status,
+                soname: mach.name.map(|name| name.to_owned()),
+                // the first entry stands for the binary itself
+                needed: mach
+                    .libs
+                    .iter()
+                    .skip(1)
+                    .map(|lib| lib.to_string())
+                    .collect(),
+                buffer,
+                elf_ctx: None,
+                elf_dynamic: None,
+                debug_buffer: None,
+                debug_sections: Vec::new(),
+            })
+        }
+
+        Object::Elf(elf) if matches!(placement, Placement::FileOffset(_)) => {
+            let offset = elf_load_bias(&elf, addr, file_offset).ok_or_else(|| {
+                format_err!(
+                    "Failed to find PT_LOAD program header for map at {:#x} in {}",
+                    addr,
+                    filename.display()
+                )
+            })?;
+
+            let sections: Vec<Section> = elf
+                .section_headers
+                .iter()
+                .map(|header| {
+                    let name = elf.shdr_strtab.get_at(header.sh_name).unwrap_or("");
+                    Section::from_elf(name, header, offset)
+                })
+                .collect();
+
+            // pick the BSS by name: the first SHT_NOBITS section is often .tbss, which holds
+            // the thread local storage image rather than the process wide globals
+            let bss = sections
+                .iter()
+                .find(|section| section.name == ".bss")
+                .or_else(|| {
+                    sections
+                        .iter()
+                        .find(|section| section.kind == SectionKind::ZeroFill)
+                })
+                .ok_or_else(|| {
+                    format_err!(
+                        "Failed to find BSS section header in {}",
+                        filename.display()
+                    )
+                })?;
+            let (bss_addr, bss_size) = (bss.addr, bss.size);
+
+            // the executable is always TLS module 1, the ids of libraries depend on the
+            // order they were loaded in
+            let tls = elf_tls(&elf, offset).map(|tls| TlsInfo {
+                module_id: if is_bin { Some(1) } else { None },
+                ..tls
+            });
+
+            add_elf_symbols(
+                &elf.syms,
+                &elf.strtab,
+                offset,
+                SymbolOrigin::Symtab,
+                source,
+                &mut symbols,
+            );
+            add_elf_dynamic_symbols(&elf, offset, source, &mut symbols);
+
+            // stripped binaries (like most distro packaged libpython/libruby) only have .dynsym,
+            // load up static symbols from the separate debug file if we can find it. Like gdb,
+            // look it up by build-id first and fall back to the .gnu_debuglink section.
+            let build_id = debug_file::build_id(&elf, &buffer);
+            let debug_file = build_id
+                .as_ref()
+                .and_then(|build_id| {
+                    debug_file::find_build_id_file(&root, &target.debug_search_paths, build_id)
+                })
+                .or_else(|| {
+                    debug_file::debuglink(&elf, &buffer).and_then(|(name, crc)| {
+                        debug_file::find_debuglink_file(
+                            &root,
+                            &target.debug_search_paths,
+                            path,
+                            &name,
+                            crc,
+                        )
+                    })
+                });
+            let mut debug_filename = None;
+            let mut debug_buffer = None;
+            let mut debug_sections = Vec::new();
+            if let Some(debug_file) = debug_file {
+                let buffer = Arc::new(debug_file.buffer);
+                match goblin::elf::Elf::parse(&buffer) {
+                    Ok(debug_elf) => {
+                        let source = symbols.add_source(buffer.clone());
+                        add_debug_symbols(
+                            &debug_elf,
+                            offset,
+                            SymbolOrigin::DebugFile,
+                            source,
+                            &mut symbols,
+                        );
+                        debug_sections = debug_elf
+                            .section_headers
+                            .iter()
+                            .map(|header| {
+                                let name = debug_elf.shdr_strtab.get_at(header.sh_name);
+                                Section::from_elf(name.unwrap_or(""), header, offset)
+                            })
+                            .collect();
+                        debug_filename = Some(debug_file.path);
+                        debug_buffer = Some(buffer.clone());
+                    }
+                    Err(e) => warn!(
+                        "Failed to parse debug file {}: {}",
+                        debug_file.path.display(),
+                        e
+                    ),
+                }
+            }
+
+            // Fedora/RHEL binaries also embed a compressed minimal symbol table, which is
+            // enough to find the static symbols we need when the debug file isn't installed
+            if debug_filename.is_none() {
+                if let Some(mini_debuginfo) = debug_file::mini_debuginfo(&elf, &buffer) {
+                    let mini_debuginfo = Arc::new(mini_debuginfo);
+                    match goblin::elf::Elf::parse(&mini_debuginfo) {
+                        Ok(mini_elf) => {
+                            let source = symbols.add_source(mini_debuginfo.clone());
+                            add_debug_symbols(
+                                &mini_elf,
+                                offset,
+                                SymbolOrigin::MiniDebugInfo,
+                                source,
+                                &mut symbols,
+                            )
+                        }
+                        Err(e) => warn!(
+                            "Failed to parse .gnu_debugdata of {}: {}",
+                            filename.display(),
+                            e
+                        ),
+                    }
+                }
+            }
+
+            Ok(BinaryInfo {
+                filename: filename.to_owned(),
+                arch: Architecture::from_elf_machine(elf.header.e_machine, elf.is_64),
+                class: Class::from_is_64(elf.is_64),
+                endianness: Endianness::from_is_little(elf.little_endian),
+                symbols,
+                bss_addr,
+                bss_size,
+                offset,
+                load_bias: offset,
+                addr,
+                size,
+                debug_filename,
+                build_id,
+                sections,
+                tls,
+                usdt_probes: usdt::elf_probes(&elf, &buffer, offset),
+                file_status,
+                soname: elf.soname.map(|name| name.to_owned()),
+                needed: elf.libraries.iter().map(|lib| lib.to_string()).collect(),
+                elf_ctx: Some(section::elf_ctx(&elf)),
+                elf_dynamic: elf
+                    .program_headers
+                    .iter()
+                    .find(|header| header.p_type == goblin::elf::program_header::PT_DYNAMIC)
+                    .map(|header| (header.p_vaddr.wrapping_add(offset), header.p_memsz)),
+                buffer,
+                debug_buffer,
+                debug_sections,
+            })
+        }
+        Object::PE(pe) if matches!(placement, Placement::FileOffset(_)) => {
+            let offset = addr;
+            let sections: Vec<Section> = pe
+                .sections
+                .iter()
+                .map(|section| Section::from_pe(section, offset))
+                .collect();
+
+            let arch = Architecture::from_coff_machine(pe.header.coff_header.machine);
+            let class = Class::from_is_64(pe.is_64);
+            let soname = pe.name.map(|name| name.to_owned());
+            let needed: Vec<String> = pe.libraries.iter().map(|lib| lib.to_string()).collect();
+            for export in pe.exports {
+                if let (Some(name), Some(export_offset)) = (export.name, export.offset) {
+                    let mut symbol =
+                        Symbol::new(export_offset as u64 + offset, SymbolOrigin::PeExports);
+                    symbol.kind = match sections
+                        .iter()
+                        .find(|section| section.contains(symbol.address))
+                    {
+                        Some(section) if section.kind == SectionKind::Code => SymbolKind::Function,
+                        Some(_) => SymbolKind::Object,
+                        None => SymbolKind::Other,
+                    };
+                    symbols.insert_from_source(source, name, symbol, true);
+                }
+            }
+            symbol::infer_sizes(&mut symbols, &sections);
+
+            pe.sections
+                .iter()
+                .find(|section| section.name.starts_with(b".data"))
+                .ok_or_else(|| {
+                    format_err!(
+                        "Failed to find .data section in PE binary of {}",
+                        filename.display()
+                    )
+                })
+                .map(|data_section| {
+                    let bss_addr = u64::from(data_section.virtual_address) + offset;
+                    let bss_size = u64::from(data_section.virtual_size);
+
+                    BinaryInfo {
+                        filename: filename.to_owned(),
+                        arch,
+                        class,
+                        endianness: Endianness::Little,
+                        symbols,
+                        bss_addr,
+                        bss_size,
+                        offset,
+                        load_bias: offset,
+                        addr,
+                        size,
+                        debug_filename: None,
+                        build_id: None,
+                        sections,
+                        tls: None,
+                        usdt_probes: Vec::new(),
+                        file_status,
+                        soname,
+                        needed,
+                        buffer,
+                        elf_ctx: None,
+                        elf_dynamic: None,
+                        debug_buffer: None,
+                        debug_sections: Vec::new(),
+                    }
+                })
+        }
+        Object::Elf(_) | Object::PE(_) => Err(format_err!(
+            "Failed to place {} by segment, which is only supported for Mach-O binaries",
+            filename.display()
+        )),
+        _ => Err(format_err!("Unhandled binary type")),
+    }
+}
+
+/// Computes the load bias of an ELF binary from one of its memory maps.
+///
+/// The map at `addr` holds the file contents starting at `file_offset`, so the bias follows
+/// from the `PT_LOAD` segment containing that offset. This doesn't depend on which segment
+/// holds the code (lld's `-z separate-code` layout puts it in the second one) and also works
+/// for non-PIE executables and prelinked libraries, where the bias can be 0 or wrap around.
+///
+/// The last page of a segment can be mapped a second time as the first page of the next
+/// one, and maps of that page can't be told apart by their offset alone. In that case the
+/// segment starting closest to the page is used, so callers should prefer passing the
+/// executable map or the first map of the binary.
+pub(crate) fn elf_load_bias(
+    elf: &goblin::elf::Elf,
+    addr: u64,
+    file_offset: Option<u64>,
+) -> Option<u64> {
+    use goblin::elf::header::ET_EXEC;
+    use goblin::elf::program_header::{PF_X, PT_LOAD};
+
+    // non-PIE executables are always loaded at the addresses they were linked for
+    if elf.header.e_type == ET_EXEC {
+        return Some(0);
+    }
+
+    let loads = || {
+        elf.program_headers
+            .iter()
+            .filter(|header| header.p_type == PT_LOAD)
+    };
+
+    if let Some(file_offset) = file_offset {
+        // maps start at the page holding the start of a segment, which can be shared with the
+        // end of the previous segment, so prefer the segment starting closest after the map's
+        // offset. Maps that start in the middle of a segment come from it being split up by
+        // mprotect (like the read-only part of the data segment after relocation).
+        //
+        // The page is found with the page size rather than p_align, which can be 2 MiB and
+        // would take in the next segment for maps in the middle of one. 16 and 64 KiB pages
+        // are used on some aarch64 and ppc64 systems, for segments aligned to them.
+        let starts_segment = |page_size: u64| {
+            loads()
+                .filter(move |header| {
+                    header.p_align.max(0x1000) >= page_size
+                        && header.p_offset & !(page_size - 1) == file_offset
+                })
+                .min_by_key(|header| header.p_offset - file_offset)
+        };
+        let segment = [0x1000, 0x4000, 0x10000]
+            .iter()
+            .find_map(|&page_size| starts_segment(page_size))
+            .or_else(|| {
+                loads().find(|header| {
+                    file_offset >= header.p_offset
+                        && file_offset < header.p_offset + header.p_filesz
+                })
+            });
+        if let Some(header) = segment {
+            return Some(
+                addr.wrapping_sub(header.p_vaddr)
+                    .wrapping_add(header.p_offset)
+                    .wrapping_sub(file_offset),
+            );
+        }
+        warn!(
+            "Failed to find PT_LOAD segment containing file offset {:#x}",
+            file_offset
+        );
+    }
+
+    // without a file offset, assume that the map starts at the page holding the
+    // first executable segment
+    let header = loads().find(|header| header.p_flags & PF_X != 0)?;
+    Some(addr.wrapping_sub(header.p_vaddr & !0xfff))
+}
+
+/// Returns the `PT_TLS` segment of an ELF binary loaded at `load_bias`, without a module id
+pub(crate) fn elf_tls(elf: &goblin::elf::Elf, load_bias: u64) -> Option<TlsInfo> {
+    elf.program_headers
+        .iter()
+        .find(|header| header.p_type == goblin::elf::program_header::PT_TLS)
+        .map(|header| TlsInfo {
+            image_addr: header.p_vaddr.wrapping_add(load_bias),
+            image_size: header.p_filesz,
+            size: header.p_memsz,
+            align: header.p_align,
+            module_id: None,
+        })
+}
+
+/// Adds the defined symbols from an ELF symbol table, replacing existing symbols with the
+/// same name.
+fn add_elf_symbols(
+    syms: &goblin::elf::Symtab,
+    strtab: &goblin::strtab::Strtab,
+    offset: u64,
+    origin: SymbolOrigin,
+    source: u32,
+    symbols: &mut SymbolMap,
+) {
+    for sym in syms.iter() {
+        if sym.st_shndx == goblin::elf::section_header::SHN_UNDEF as usize {
+            continue;
+        }
+        if let Some(name) = strtab.get_at(sym.st_name) {
+            if !name.is_empty() {
+                let symbol = Symbol::from_elf(&sym, offset, origin);
+                symbols.insert_from_source(source, name, symbol, true);
+            }
+        }
+    }
+}
+
+/// Adds the defined symbols from `.dynsym`, taking GNU symbol versions into account.
+///
+/// Versioned symbols are added as `name@VERSION`, and the default version (`name@@VERSION`)
+/// is also added under the bare name. This matches what `dlsym` returns: the default version,
+/// and nothing for symbols that only have hidden versions.
+fn add_elf_dynamic_symbols(
+    elf: &goblin::elf::Elf,
+    offset: u64,
+    source: u32,
+    symbols: &mut SymbolMap,
+) {
+    let versym = match &elf.versym {
+        Some(versym) => versym,
+        None => {
+            return add_elf_symbols(
+                &elf.dynsyms,
+                &elf.dynstrtab,
+                offset,
+                SymbolOrigin::Dynsym,
+                source,
+                symbols,
+            )
+        }
+    };
+
+    // the version indices used in .gnu.version, mapped to the version names. The base
+    // definition is the name of the binary itself, and symbols using it aren't versioned.
+    let mut versions = HashMap::new();
+    if let Some(verdef) = &elf.verdef {
+        for definition in verdef.iter() {
+            if definition.vd_flags & goblin::elf::symver::VER_FLG_BASE != 0 {
+                continue;
+            }
+            let name = definition
+                .iter()
+                .next()
+                .and_then(|aux| elf.dynstrtab.get_at(aux.vda_name));
+            if let Some(name) = name {
+                versions.insert(definition.vd_ndx, name);
+            }
+        }
+    }
+
+    for (index, sym) in elf.dynsyms.iter().enumerate() {
+        if sym.st_shndx == goblin::elf::section_header::SHN_UNDEF as usize {
+            continue;
+        }
+        let name = match elf.dynstrtab.get_at(sym.st_name) {
+            Some(name) if !name.is_empty() => name,
+            _ => continue,
+        };
+        let symbol = Symbol::from_elf(&sym, offset, SymbolOrigin::Dynsym);
+        let versym = versym.get_at(index);
+        let version = versym
+            .as_ref()
+            .and_then(|versym| versions.get(&versym.version()));
+        match (version, versym) {
+            (Some(&version), Some(versym)) => {
+                // the linker defines an absolute symbol named after each version
+                if version == name {
+                    continue;
+                }
+                if !versym.is_hidden() {
+                    symbols.insert_from_source(source, name, symbol.clone(), true);
+                }
+                symbols.insert(format!("{}@{}", name, version), symbol);
+            }
+            _ => symbols.insert_from_source(source, name, symbol, true),
+        }
+    }
+}
+
+/// Adds the defined symbols from a separate debug file, without overriding symbols
+/// that were already read from the binary itself.
+fn add_debug_symbols(
+    debug_elf: &goblin::elf::Elf,
+    offset: u64,
+    origin: SymbolOrigin,
+    source: u32,
+    symbols: &mut SymbolMap,
+) {
+    for sym in debug_elf.syms.iter() {
+        if sym.st_shndx == goblin::elf::section_header::SHN_UNDEF as usize {
+            continue;
+        }
+        if let Some(name) = debug_elf.strtab.get_at(sym.st_name) {
+            if !name.is_empty() {
+                let symbol = Symbol::from_elf(&sym, offset, origin);
+                symbols.insert_from_source(source, name, symbol, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    // where the tests load fixture libraries
+    pub(crate) const BASE: u64 = 0x7f00_0000_0000;
+
+    // parses a fixture loaded at BASE, from its executable map (the executable PT_LOAD of the
+    // fixtures starts at 0x1000)
+    pub(crate) fn parse_fixture(name: &str) -> BinaryInfo {
+        parse_binary(
+            &Target::host(),
+            &fixture(name),
+            BASE + 0x1000,
+            0x1000,
+            Some(0x1000),
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_binary_debuglink() {
+        // the executable PT_LOAD of the fixture starts at 0x1000
+        let info = parse_fixture("libfixture.stripped");
+
+        assert!(info
+            .debug_filename
+            .as_ref()
+            .unwrap()
+            .ends_with("tests/fixtures/libfixture.debug"));
+        // exported symbol, present in .dynsym of the stripped binary
+        assert_eq!(
+            info.symbols.address("fixture_global_data").unwrap(),
+            BASE + 0x4014
+        );
+        // static symbol, only available from the debug file
+        assert_eq!(
+            info.symbols.address("fixture_static_counter").unwrap(),
+            BASE + 0x4010
+        );
+        assert_eq!(
+            info.build_id.as_deref(),
+            Some("6a42ee62f5455ca29dcdb5bc9aac278e24fa11d3")
+        );
+    }
+
+    #[test]
+    fn test_parse_binary_symbol_metadata() {
+        let info = parse_fixture("libfixture.stripped");
+
+        let function = info.symbols.get("fixture_function").unwrap();
+        assert_eq!(function.kind, SymbolKind::Function);
+        assert_eq!(function.binding, SymbolBinding::Global);
+        assert_eq!(function.visibility, SymbolVisibility::Default);
+        assert_eq!(function.origin, SymbolOrigin::Dynsym);
+        assert_eq!(function.size, 133);
+        assert!(function.contains(function.address + 132));
+        assert!(!function.contains(function.address + 133));
+
+        let counter = info.symbols.get("fixture_static_counter").unwrap();
+        assert_eq!(counter.kind, SymbolKind::Object);
+        assert_eq!(counter.binding, SymbolBinding::Local);
+        assert_eq!(counter.origin, SymbolOrigin::DebugFile);
+        assert_eq!(counter.size, 4);
+
+        let tls = info.symbols.get("fixture_tls_counter").unwrap();
+        assert_eq!(tls.kind, SymbolKind::Tls);
+
+        // imports are undefined in this binary, and shouldn't show up as symbols
+        assert!(!info.symbols.contains_key("__cxa_finalize"));
+    }
+
+    #[test]
+    fn test_parse_binary_mini_debuginfo() {
+        let info = parse_fixture("libfixture.minidebug");
+
+        assert_eq!(info.debug_filename, None);
+        assert_eq!(
+            info.symbols.address("fixture_static_counter").unwrap(),
+            BASE + 0x4010
+        );
+    }
+
+    #[test]
+    fn test_parse_binary_sections() {
+        let info = parse_fixture("libfixture.stripped");
+
+        // .tbss is the first SHT_NOBITS section, but isn't the BSS
+        let tbss = info.section(".tbss").unwrap();
+        assert_eq!(tbss.kind, SectionKind::ThreadLocal);
+        assert_eq!(info.bss_addr, BASE + 0x4018);
+        assert_eq!(info.bss_size, 8);
+
+        let data = info.section(".data").unwrap();
+        assert_eq!(data.kind, SectionKind::Data);
+        assert_eq!(data.range(), BASE + 0x4008..BASE + 0x4018);
+        assert_eq!(
+            info.section_containing(info.symbols.address("fixture_bss_data").unwrap())
+                .map(|section| section.name.as_str()),
+            Some(".bss")
+        );
+
+        // .data and .bss are adjacent, so show up as a single range. Thread local storage
+        // and sections that aren't loaded are never included.
+        let ranges = info.writable_data_ranges();
+        assert!(ranges
+            .iter()
+            .any(|range| range.start <= BASE + 0x4008 && range.end == BASE + 0x4020));
+        let tdata = info.section(".tdata").unwrap();
+        assert!(!ranges.iter().any(|range| range.contains(&tdata.addr)));
+        assert!(!ranges.iter().any(|range| range.start < BASE));
+    }
+
+    #[test]
+    fn test_parse_binary_load_bias() {
+        let bias = |name: &str, addr: u64, file_offset: Option<u64>| {
+            parse_binary(
+                &Target::host(),
+                &fixture(name),
+                addr,
+                0x1000,
+                file_offset,
+                false,
+            )
+            .unwrap()
+            .load_bias
+        };
+
+        // maps of the binary give the same bias, including the read-only first segment
+        // that comes before the code, and the writable part of the data segment that
+        // starts in the middle of it
+        assert_eq!(
+            bias("libfixture.stripped", BASE + 0x1000, Some(0x1000)),
+            BASE
+        );
+        assert_eq!(bias("libfixture.stripped", BASE, Some(0)), BASE);
+        assert_eq!(
+            bias("libfixture.stripped", BASE + 0x4000, Some(0x3000)),
+            BASE
+        );
+        assert_eq!(bias("libfixture.stripped", BASE + 0x1000, None), BASE);
+
+        // with 2 MiB aligned segments, a map in the middle of the code segment isn't taken
+        // for the data segment that starts less than 2 MiB after it
+        assert_eq!(
+            bias("libfixture.hugepage", BASE + 0x1000, Some(0x1000)),
+            BASE
+        );
+        assert_eq!(bias("libfixture.hugepage", BASE, Some(0)), BASE);
+        assert_eq!(
+            bias("libfixture.hugepage", BASE + 0x20_2000, Some(0x2000)),
+            BASE
+        );
+
+        // non-PIE executables aren't relocated
+        let info = parse_binary(
+            &Target::host(),
+            &fixture("fixture.nopie"),
+            0x401000,
+            0x1000,
+            Some(0x1000),
+            false,
+        )
+        .unwrap();
+        assert_eq!(info.load_bias, 0);
+        assert_eq!(info.symbols.address("fixture_function"), Some(0x401000));
+
+        // a library linked at a higher address than it was loaded at has a "negative" bias
+        let info = parse_fixture("libfixture.prelinked");
+        assert_eq!(info.load_bias, BASE.wrapping_sub(0x7fff_0000_0000));
+        assert_eq!(
+            info.symbols.address("fixture_global_data"),
+            Some(BASE + 0x4014)
+        );
+        assert_eq!(info.bss_addr, BASE + 0x4018);
+    }
+
+    #[test]
+    fn test_section_data_compressed() {
+        let full = parse_fixture("libfixture.full");
+        let expected = full.section_data(".debug_info").unwrap();
+        assert!(!full.section(".debug_info").unwrap().compressed);
+        assert!(!expected.is_empty());
+
+        for name in &["libfixture.zlib", "libfixture.zstd"] {
+            let info = parse_fixture(name);
+            let section = info.section(".debug_info").unwrap();
+            assert!(section.compressed, "{}", name);
+            assert!(section.file_size < expected.len() as u64);
+            assert_eq!(info.section_data(".debug_info").unwrap(), expected);
+            // sections that weren't compressed are read as is
+            assert_eq!(
+                info.section_data(".debug_abbrev").unwrap(),
+                full.section_data(".debug_abbrev").unwrap()
+            );
+        }
+
+        assert!(full.section_data(".bss").unwrap().is_empty());
+        assert!(full.section_data(".missing").is_err());
+
+        // a section that decompresses to more than its header says is only decoded a byte
+        // past that size
+        use std::io::Write;
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&vec![0; 0x100000]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        // Elf64_Chdr: ch_type, ch_reserved, ch_size and ch_addralign
+        let mut data = [1u32.to_le_bytes(), [0; 4]].concat();
+        data.extend_from_slice(&16u64.to_le_bytes());
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&compressed);
+        let section = Section {
+            name: ".debug_info".to_owned(),
+            segment: None,
+            addr: 0,
+            size: 16,
+            file_offset: 0,
+            file_size: data.len() as u64,
+            compressed: true,
+            flags: 0,
+            kind: SectionKind::NotLoaded,
+            writable: false,
+        };
+        let ctx = goblin::container::Ctx::new(
+            goblin::container::Container::Big,
+            goblin::container::Endian::Little,
+        );
+        let err = section::section_data(&data, &section, Some(ctx)).unwrap_err();
+        assert!(
+            err.to_string().contains("decompressed to 17 bytes"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_parse_binary_symbol_versions() {
+        let info = parse_fixture("libversioned.stripped");
+
+        // the bare name is the default version, no matter the order of .dynsym
+        let old = info.symbols.address("versioned@VERS_1").unwrap();
+        let new = info.symbols.address("versioned@VERS_2").unwrap();
+        assert_ne!(old, new);
+        assert_eq!(info.symbols.address("versioned"), Some(new));
+
+        // symbols with only a hidden version need the version to be looked up
+        assert!(info.symbols.contains_key("legacy@VERS_1"));
+        assert!(!info.symbols.contains_key("legacy"));
+
+        assert_eq!(
+            info.symbols.address("versioned_data"),
+            info.symbols.address("versioned_data@VERS_1")
+        );
+        assert!(!info.symbols.contains_key("VERS_1"));
+        assert!(!info.symbols.contains_key("versioned@@VERS_2"));
+    }
+
+    #[test]
+    fn test_parse_binary_fat_macho() {
+        let base = 0x1_0000_0000;
+        let parse = |arch| {
+            parse_binary(
+                &Target::host().with_arch(arch),
+                &fixture("macho.fat"),
+                base,
+                0x1000,
+                None,
+                false,
+            )
+        };
+
+        for (arch, marker, data_addr) in [
+            (Architecture::X86, "fixture_i386", 0x8),
+            (Architecture::X86_64, "fixture_x86_64", 0x10),
+            (Architecture::Aarch64, "fixture_arm64", 0x20),
+            (Architecture::Arm64e, "fixture_arm64e", 0x20),
+        ] {
+            let info = parse(Some(arch)).unwrap();
+            assert_eq!(info.arch, arch);
+            assert!(info.symbols.contains_key(marker));
+            assert_eq!(
+                info.symbols.address("fixture_global_data"),
+                Some(base + data_addr)
+            );
+            assert_eq!(info.bss_addr, base + data_addr + 8);
+            // sections are read from the slice, not the start of the file
+            assert_eq!(
+                &info.section_data("__data").unwrap()[..],
+                &[42, 0, 0, 0, 1, 0, 0, 0]
+            );
+        }
+
+        let err = parse(Some(Architecture::PowerPc)).err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("(found x86, x86_64, aarch64, arm64e)"));
+
+        // without an explicit arch the one spytools is running on is used
+        if cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+            assert_eq!(parse(None).unwrap().arch, Architecture::native());
+        }
+    }
+
+    #[test]
+    fn test_parse_binary_elf32() {
+        let info = parse_fixture("libfixture32.stripped");
+        assert_eq!(info.arch, Architecture::X86);
+        assert_eq!(info.class, Class::Bits32);
+        assert_eq!(info.class.pointer_size(), 4);
+        assert_eq!(info.endianness, Endianness::Little);
+        assert_eq!(info.load_bias, BASE);
+        assert!(info.build_id.is_some());
+
+        let function = info.symbols.get("fixture_function").unwrap();
+        assert_eq!(function.address, BASE + 0x1000);
+        assert_eq!(function.size, 6);
+        assert_eq!(function.kind, SymbolKind::Function);
+        assert_eq!(
+            info.symbols.address("fixture_global_data"),
+            Some(BASE + 0x4000)
+        );
+        assert_eq!((info.bss_addr, info.bss_size), (BASE + 0x4004, 4));
+
+        let tls = info.tls.as_ref().unwrap();
+        assert_eq!((tls.image_addr, tls.size, tls.align), (BASE + 0x3f9c, 4, 4));
+        let tls_value = info.symbols.get("fixture_tls_value").unwrap();
+        assert_eq!((tls_value.kind, tls_value.address), (SymbolKind::Tls, 0));
+
+        assert_eq!(info.usdt_probes.len(), 1);
+        assert_eq!(info.usdt_probes[0].address, BASE + 0x1003);
+        assert_eq!(info.usdt_probes[0].arguments, vec!["-4@8(%ebp)"]);
+        assert_eq!(info.symbolize(BASE + 0x1003), Some(("fixture_function", 3)));
+    }
+
+    #[test]
+    fn test_parse_binary_target() {
+        // binaries copied from another machine are read relative to its root
+        let target = Target::root(fixture(""));
+        let info = parse_binary(
+            &target,
+            Path::new("/libfixture.stripped"),
+            BASE + 0x1000,
+            0x1000,
+            Some(0x1000),
+            false,
+        )
+        .unwrap();
+        assert_eq!(info.filename, fixture("libfixture.stripped"));
+        assert_eq!(info.load_bias, BASE);
+
+        // libraries from the dyld shared cache are placed by one of their segments
+        let target = target.with_arch(Some(Architecture::X86_64));
+        let info =
+            parse_mach_segment(&target, Path::new("/macho.fat"), "", BASE + 0x8000, 0x20).unwrap();
+        assert_eq!(info.load_bias, BASE + 0x8000);
+        assert_eq!(
+            info.symbols.address("fixture_global_data"),
+            Some(BASE + 0x8010)
+        );
+        assert_eq!((info.bss_addr, info.bss_size), (BASE + 0x8000, 0x20));
+        assert!(parse_mach_segment(&target, Path::new("/macho.fat"), "__LINKEDIT", 0, 0).is_err());
+        assert!(
+            parse_mach_segment(&target, Path::new("/libfixture.stripped"), "__DATA", 0, 0).is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_binary_replaced() {
+        use std::os::unix::fs::symlink;
+
+        // a proc tree where map_files links to a hard link of the mapped file, which keeps
+        // pointing at it once the file at its path is replaced, like the kernel's links do
+        let dir = std::env::temp_dir().join(format!("spytools-replaced-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let pid: remoteprocess::Pid = 2_000_000_000;
+        let process_dir = dir.join("proc").join(pid.to_string());
+        std::fs::create_dir_all(process_dir.join("map_files")).unwrap();
+        std::fs::create_dir_all(dir.join("root/lib")).unwrap();
+        std::fs::create_dir_all(dir.join("mapped")).unwrap();
+        symlink(dir.join("root"), process_dir.join("root")).unwrap();
+        let path = dir.join("root/lib/libfixture.so");
+        std::fs::copy(fixture("libfixture.stripped"), &path).unwrap();
+        std::fs::hard_link(&path, dir.join("mapped/libfixture.so")).unwrap();
+        let (addr, size) = (BASE + 0x1000, 0x1000);
+        let map_files = process_dir.join(format!("map_files/{:x}-{:x}", addr, addr + size));
+        symlink(dir.join("mapped/libfixture.so"), &map_files).unwrap();
+
+        let procfs = crate::procfs::Procfs::new(dir.join("proc"));
+        let target = Target::process_in(&procfs, pid);
+        let parse =
+            |path: &str| parse_binary(&target, Path::new(path), addr, size, Some(0x1000), false);
+        let info = parse("/lib/libfixture.so").unwrap();
+        assert_eq!(info.file_status, FileStatus::Current);
+        let in_root = process_dir.join("root/lib/libfixture.so");
+        assert_eq!(info.filename, in_root);
+
+        // a deploy replaces the file on disk, but the mapped one is still parsed
+        let replacement = dir.join("root/lib/libfixture.so.new");
+        std::fs::copy(fixture("libusdt.stripped"), &replacement).unwrap();
+        std::fs::rename(&replacement, &path).unwrap();
+        let info = parse("/lib/libfixture.so").unwrap();
+        assert_eq!(info.file_status, FileStatus::Replaced);
+        assert_eq!(info.filename, map_files);
+        assert!(info.symbols.get("fixture_global_data").is_some());
+
+        std::fs::remove_file(&path).unwrap();
+        let info = parse("/lib/libfixture.so (deleted)").unwrap();
+        assert_eq!(info.file_status, FileStatus::Deleted);
+        assert!(info.symbols.get("fixture_global_data").is_some());
+
+        // without access to map_files, a deleted file can't be read
+        std::fs::remove_file(&map_files).unwrap();
+        let (filename, status) =
+            target.mapped_file(Path::new("/lib/libfixture.so (deleted)"), false, addr, size);
+        assert_eq!((filename, status), (in_root, FileStatus::Deleted));
+        assert!(parse("/lib/libfixture.so (deleted)").is_err());
+
+        // nor can it be checked whether a file is current
+        let path = Path::new("/lib/libfixture.so");
+        assert_eq!(
+            target.mapped_file(path, false, addr, size).1,
+            FileStatus::Unknown
+        );
+        assert_eq!(
+            Target::root(dir.join("root"))
+                .mapped_file(path, false, addr, size)
+                .1,
+            FileStatus::Unknown
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_binary_usdt_probes() {
+        let info = parse_fixture("libusdt.stripped");
+
+        assert_eq!(
+            info.usdt_probes,
+            vec![
+                UsdtProbe {
+                    provider: "fixture".to_owned(),
+                    name: "tick".to_owned(),
+                    address: BASE + 0x1100,
+                    semaphore: Some(BASE + 0x4008),
+                    arguments: vec!["-4@%edi".to_owned(), "8@%rsi".to_owned()],
+                },
+                UsdtProbe {
+                    provider: "fixture".to_owned(),
+                    name: "plain".to_owned(),
+                    address: BASE + 0x1110,
+                    semaphore: None,
+                    arguments: Vec::new(),
+                },
+            ]
+        );
+        // the probes are inside the functions that fire them
+        assert_eq!(info.symbols.address("usdt_tick"), Some(BASE + 0x1100));
+        assert_eq!(
+            info.symbols.address("fixture_tick_semaphore"),
+            info.usdt_probes[0].semaphore
+        );
+    }
+
+    #[test]
+    fn test_symbolize() {
+        let info = parse_fixture("libfixture.stripped");
+
+        let function = info.symbols.address("fixture_function").unwrap();
+        assert_eq!(info.symbolize(function), Some(("fixture_function", 0)));
+        assert_eq!(
+            info.symbolize(function + 10),
+            Some(("fixture_function", 10))
+        );
+
+        let data = info.symbols.address("fixture_global_data").unwrap();
+        assert_eq!(info.symbolize(data + 3), Some(("fixture_global_data", 3)));
+        assert_ne!(
+            info.symbolize(data + 4).map(|(name, _)| name),
+            Some("fixture_global_data")
+        );
+
+        // thread local symbols don't have addresses that can be symbolized
+        let tls = info.symbols.get("fixture_tls_counter").unwrap();
+        assert_ne!(
+            info.symbolize(tls.address).map(|(name, _)| name),
+            Some("fixture_tls_counter")
+        );
+        assert_eq!(info.symbolize(BASE + 0x10_0000), None);
+    }
+}
@@ -0,0 +1,301 @@
+use std::borrow::Cow;
+use std::io::Read;
+use std::ops::Range;
+
+use anyhow::{format_err, Error};
+use goblin::container::{Container, Ctx, Endian};
+use goblin::elf::compression_header::{CompressionHeader, ELFCOMPRESS_ZLIB, ELFCOMPRESS_ZSTD};
+use goblin::elf::section_header::{
+    SectionHeader, SHF_ALLOC, SHF_COMPRESSED, SHF_EXECINSTR, SHF_TLS, SHF_WRITE, SHT_NOBITS,
+    SHT_NOTE,
+};
+use goblin::elf::Elf;
+use goblin::pe::section_table::{
+    SectionTable, IMAGE_SCN_CNT_CODE, IMAGE_SCN_CNT_UNINITIALIZED_DATA, IMAGE_SCN_MEM_WRITE,
+};
+
+/// What a section holds, normalized across the ELF, Mach-O and PE formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    /// Executable code
+    Code,
+    /// Initialized data that is writable at runtime
+    Data,
+    /// Initialized data that is read-only at runtime
+    ReadOnlyData,
+    /// Zero filled data that takes no space in the file (`.bss`, `__bss`, `__common`)
+    ZeroFill,
+    /// The initialization image for thread local storage (`.tdata`, `.tbss`)
+    ThreadLocal,
+    /// ELF notes
+    Note,
+    /// Sections that aren't loaded into memory, like symbol tables and debug info
+    NotLoaded,
+}
+
+/// A section of a binary, with its address relocated to where it is loaded in the process
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// The name of the section, like `.bss` or `__bss`
+    pub name: String,
+    /// The name of the segment containing the section (only set for Mach-O)
+    pub segment: Option<String>,
+    /// The address of the section in the process's memory, or 0 if it isn't loaded
+    pub addr: u64,
+    /// The size in bytes of the section in memory
+    pub size: u64,
+    /// The offset of the section's data in the file (meaningless for zero filled sections)
+    pub file_offset: u64,
+    /// The size in bytes of the section's data in the file, which is 0 for zero filled
+    /// sections and the compressed size for compressed sections
+    pub file_size: u64,
+    /// Whether the section's data is compressed in the file (ELF `SHF_COMPRESSED`)
+    pub compressed: bool,
+    /// The raw format specific flags (`sh_flags`, Mach-O section flags or PE characteristics)
+    pub flags: u64,
+    /// What the section holds
+    pub kind: SectionKind,
+    /// Whether the section is mapped writable
+    pub writable: bool,
+}
+
+impl Section {
+    /// Returns the range of memory addresses occupied by the section
+    pub fn range(&self) -> Range<u64> {
+        self.addr..self.addr + self.size
+    }
+
+    /// Returns `true` if `addr` is inside of the section
+    pub fn contains(&self, addr: u64) -> bool {
+        self.addr != 0 && self.range().contains(&addr)
+    }
+
+    /// Returns `true` if this section holds writable, non thread local data
+    /// (like `.data`, `.bss` or `.PyRuntime`)
+    pub fn is_writable_data(&self) -> bool {
+        self.writable
+            && self.addr != 0
+            && matches!(self.kind, SectionKind::Data | SectionKind::ZeroFill)
+    }
+
+    pub(crate) fn from_elf(name: &str, header: &SectionHeader, offset: u64) -> Section {
+        let loaded = header.sh_flags & u64::from(SHF_ALLOC) != 0;
+        let kind = if !loaded {
+            SectionKind::NotLoaded
+        } else if header.sh_flags & u64::from(SHF_TLS) != 0 {
+            SectionKind::ThreadLocal
+        } else if header.sh_type == SHT_NOBITS {
+            SectionKind::ZeroFill
+        } else if header.sh_type == SHT_NOTE {
+            SectionKind::Note
+        } else if header.sh_flags & u64::from(SHF_EXECINSTR) != 0 {
+            SectionKind::Code
+        } else if header.sh_flags & u64::from(SHF_WRITE) != 0 {
+            SectionKind::Data
+        } else {
+            SectionKind::ReadOnlyData
+        };
+
+        Section {
+            name: name.to_owned(),
+            segment: None,
+            addr: if loaded {
+                header.sh_addr.wrapping_add(offset)
+            } else {
+                0
+            },
+            size: header.sh_size,
+            file_offset: header.sh_offset,
+            file_size: if header.sh_type == SHT_NOBITS {
+                0
+            } else {
+                header.sh_size
+            },
+            compressed: header.sh_flags & u64::from(SHF_COMPRESSED) != 0,
+            flags: header.sh_flags,
+            kind,
+            writable: header.sh_flags & u64::from(SHF_WRITE) != 0,
+        }
+    }
+
+    pub(crate) fn from_mach(
+        section: &goblin::mach::segment::Section,
+        segment_initprot: u32,
+        offset: u64,
+    ) -> Result<Section, goblin::error::Error> {
+        use goblin::mach::constants::*;
+
+        let writable = segment_initprot & 2 != 0; // VM_PROT_WRITE
+        let kind = match section.flags & SECTION_TYPE {
+            S_ZEROFILL | S_GB_ZEROFILL => SectionKind::ZeroFill,
+            S_THREAD_LOCAL_REGULAR | S_THREAD_LOCAL_ZEROFILL | S_THREAD_LOCAL_VARIABLES => {
+                SectionKind::ThreadLocal
+            }
+            _ if section.flags & (S_ATTR_PURE_INSTRUCTIONS | S_ATTR_SOME_INSTRUCTIONS) != 0 => {
+                SectionKind::Code
+            }
+            _ if writable => SectionKind::Data,
+            _ => SectionKind::ReadOnlyData,
+        };
+
+        Ok(Section {
+            name: section.name()?.to_owned(),
+            segment: Some(section.segname()?.to_owned()),
+            addr: section.addr.wrapping_add(offset),
+            size: section.size,
+            file_offset: u64::from(section.offset),
+            file_size: if kind == SectionKind::ZeroFill {
+                0
+            } else {
+                section.size
+            },
+            compressed: false,
+            flags: u64::from(section.flags),
+            kind,
+            writable,
+        })
+    }
+
+    pub(crate) fn from_pe(section: &SectionTable, offset: u64) -> Section {
+        let writable = section.characteristics & IMAGE_SCN_MEM_WRITE != 0;
+        let kind = if section.characteristics & IMAGE_SCN_CNT_CODE != 0 {
+            SectionKind::Code
+        } else if section.characteristics & IMAGE_SCN_CNT_UNINITIALIZED_DATA != 0 {
+            SectionKind::ZeroFill
+        } else if writable {
+            SectionKind::Data
+        } else {
+            SectionKind::ReadOnlyData
+        };
+
+        Section {
+            name: section
+                .name()
+                .map(|name| name.to_owned())
+                .unwrap_or_else(|_| String::from_utf8_lossy(&section.name).into_owned()),
+            segment: None,
+            addr: u64::from(section.virtual_address) + offset,
+            size: u64::from(section.virtual_size),
+            file_offset: u64::from(section.pointer_to_raw_data),
+            file_size: u64::from(section.size_of_raw_data),
+            compressed: false,
+            flags: u64::from(section.characteristics),
+            kind,
+            writable,
+        }
+    }
+}
+
+/// Returns the contents of a section from the file it was read from, decompressing sections
+/// with `SHF_COMPRESSED` set. `ctx` gives the class and byte order of ELF files, which is
+/// needed to read the compression header.
+///
+/// Zero filled sections don't have any contents in the file, and are returned empty rather
+/// than allocating their size, which comes from the file and can't be trusted.
+pub(crate) fn section_data<'a>(
+    buffer: &'a [u8],
+    section: &Section,
+    ctx: Option<Ctx>,
+) -> Result<Cow<'a, [u8]>, Error> {
+    if section.kind == SectionKind::ZeroFill {
+        return Ok(Cow::Borrowed(&[]));
+    }
+    let data = buffer
+        .get(section.file_offset as usize..)
+        .and_then(|data| data.get(..section.file_size as usize))
+        .ok_or_else(|| format_err!("Section {} is out of bounds of the file", section.name))?;
+    if !section.compressed {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    let ctx = ctx.ok_or_else(|| {
+        format_err!(
+            "Section {} is compressed, but isn't from an ELF file",
+            section.name
+        )
+    })?;
+    let header = CompressionHeader::parse(data, 0, ctx)?;
+    let compressed = &data[CompressionHeader::size(ctx).min(data.len())..];
+    // don't trust the header with the size of the allocation, the data might be corrupt. The
+    // decoders are stopped a byte past the size from the header, so that data that
+    // decompresses to far more than that (like a zip bomb) is rejected without decoding it all.
+    let mut decompressed = Vec::with_capacity(header.ch_size.min(1 << 24) as usize);
+    let limit = header.ch_size.saturating_add(1);
+    match header.ch_type {
+        ELFCOMPRESS_ZLIB => {
+            flate2::read::ZlibDecoder::new(compressed)
+                .take(limit)
+                .read_to_end(&mut decompressed)?;
+        }
+        ELFCOMPRESS_ZSTD => {
+            ruzstd::decoding::StreamingDecoder::new(compressed)
+                .map_err(|e| format_err!("Failed to decompress {}: {}", section.name, e))?
+                .take(limit)
+                .read_to_end(&mut decompressed)?;
+        }
+        other => {
+            return Err(format_err!(
+                "Section {} uses unsupported compression type {}",
+                section.name,
+                other
+            ))
+        }
+    }
+    if decompressed.len() as u64 != header.ch_size {
+        return Err(format_err!(
+            "Section {} decompressed to {} bytes, expected {}",
+            section.name,
+            decompressed.len(),
+            header.ch_size
+        ));
+    }
+    Ok(Cow::Owned(decompressed))
+}
+
+/// Returns the contents of the named section of an ELF file, if it has one
+pub(crate) fn elf_section_data<'a>(
+    elf: &Elf,
+    buffer: &'a [u8],
+    name: &str,
+) -> Option<Result<Cow<'a, [u8]>, Error>> {
+    let header = elf
+        .section_headers
+        .iter()
+        .find(|header| elf.shdr_strtab.get_at(header.sh_name) == Some(name))?;
+    let section = Section::from_elf(name, header, 0);
+    Some(section_data(buffer, &section, Some(elf_ctx(elf))))
+}
+
+/// Returns the class and byte order of an ELF file
+pub(crate) fn elf_ctx(elf: &Elf) -> Ctx {
+    let container = if elf.is_64 {
+        Container::Big
+    } else {
+        Container::Little
+    };
+    let endian = if elf.little_endian {
+        Endian::Little
+    } else {
+        Endian::Big
+    };
+    Ctx::new(container, endian)
+}
+
+/// Merges the address ranges of the writable data sections, coalescing adjacent sections
+pub(crate) fn writable_data_ranges(sections: &[Section]) -> Vec<Range<u64>> {
+    let mut ranges: Vec<Range<u64>> = sections
+        .iter()
+        .filter(|section| section.is_writable_data() && section.size > 0)
+        .map(|section| section.range())
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
@@ -0,0 +1,764 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::{Arc, OnceLock};
+
+use goblin::elf::sym::{self, Sym};
+use regex::Regex;
+
+use crate::binary_parser::section::{Section, SectionKind};
+
+/// The type of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A function or other executable code
+    Function,
+    /// A data object, like a global variable
+    Object,
+    /// A thread local variable. The value of these symbols is an offset into the thread local
+    /// storage block of the module, rather than an address.
+    Tls,
+    /// A symbol without a type, or of a type that we don't care about
+    Other,
+}
+
+/// The linkage of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolBinding {
+    /// Only visible inside of the binary (like a `static` variable in C)
+    Local,
+    /// Visible to other binaries
+    Global,
+    /// Global, but can be overridden by a global symbol with the same name
+    Weak,
+}
+
+/// The visibility of a symbol to other binaries (for ELF `st_other`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolVisibility {
+    /// Visibility is given by the binding of the symbol
+    Default,
+    /// Not visible to other binaries, with processor specific semantics
+    Internal,
+    /// Not visible to other binaries
+    Hidden,
+    /// Visible to other binaries, but can't be preempted
+    Protected,
+}
+
+/// The symbol table that a symbol was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolOrigin {
+    /// The ELF `.symtab` of the binary
+    Symtab,
+    /// The ELF `.dynsym` of the binary
+    Dynsym,
+    /// The `.symtab` of a separate debug file
+    DebugFile,
+    /// The `.symtab` embedded in the `.gnu_debugdata` section of the binary
+    MiniDebugInfo,
+    /// The Mach-O symbol table
+    MachO,
+    /// The export table of a PE binary
+    PeExports,
+    /// A PDB file, loaded through the Windows symbol handler
+    Pdb,
+}
+
+/// Metadata about a symbol in a binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The address of the symbol in the process's memory. For thread local symbols this is
+    /// the offset of the symbol in the module's TLS block instead, see
+    /// `ProcessInfo::get_tls_symbol_address`.
+    pub address: u64,
+    /// The size of the symbol in bytes, or 0 if unknown
+    pub size: u64,
+    /// The type of the symbol
+    pub kind: SymbolKind,
+    /// The linkage of the symbol
+    pub binding: SymbolBinding,
+    /// The visibility of the symbol
+    pub visibility: SymbolVisibility,
+    /// The symbol table the symbol was read from
+    pub origin: SymbolOrigin,
+}
+
+impl Symbol {
+    /// Creates a global symbol with only an address known, like those that come from PDB files
+    pub fn new(address: u64, origin: SymbolOrigin) -> Symbol {
+        Symbol {
+            address,
+            size: 0,
+            kind: SymbolKind::Other,
+            binding: SymbolBinding::Global,
+            visibility: SymbolVisibility::Default,
+            origin,
+        }
+    }
+
+    /// Returns `true` if `addr` is inside of the symbol. Symbols of unknown size only
+    /// contain their own address.
+    pub fn contains(&self, addr: u64) -> bool {
+        addr == self.address || (addr > self.address && addr - self.address < self.size)
+    }
+
+    /// Returns `true` if the dynamic linker can bind references from other binaries to the
+    /// symbol, which is what lets it interpose symbols of the same name in later binaries
+    pub fn is_exported(&self) -> bool {
+        self.binding != SymbolBinding::Local
+            && matches!(
+                self.visibility,
+                SymbolVisibility::Default | SymbolVisibility::Protected
+            )
+    }
+
+    pub(crate) fn from_elf(sym: &Sym, offset: u64, origin: SymbolOrigin) -> Symbol {
+        let kind = match sym.st_type() {
+            sym::STT_FUNC | sym::STT_GNU_IFUNC => SymbolKind::Function,
+            sym::STT_OBJECT | sym::STT_COMMON => SymbolKind::Object,
+            sym::STT_TLS => SymbolKind::Tls,
+            _ => SymbolKind::Other,
+        };
+        let binding = match sym.st_bind() {
+            sym::STB_LOCAL => SymbolBinding::Local,
+            sym::STB_WEAK => SymbolBinding::Weak,
+            _ => SymbolBinding::Global,
+        };
+        let visibility = match sym.st_visibility() {
+            sym::STV_INTERNAL => SymbolVisibility::Internal,
+            sym::STV_HIDDEN => SymbolVisibility::Hidden,
+            sym::STV_PROTECTED => SymbolVisibility::Protected,
+            _ => SymbolVisibility::Default,
+        };
+        // the value of TLS symbols is an offset into the module's TLS block, which doesn't
+        // move with the load address of the module
+        let address = if kind == SymbolKind::Tls {
+            sym.st_value
+        } else {
+            sym.st_value.wrapping_add(offset)
+        };
+        Symbol {
+            address,
+            size: sym.st_size,
+            kind,
+            binding,
+            visibility,
+            origin,
+        }
+    }
+
+    pub(crate) fn from_mach(
+        nlist: &goblin::mach::symbols::Nlist,
+        sections: &[Section],
+        offset: u64,
+    ) -> Symbol {
+        use goblin::mach::symbols::{N_EXT, N_WEAK_DEF};
+
+        // n_sect is a 1-based index into all of the sections of the binary
+        let section = nlist
+            .n_sect
+            .checked_sub(1)
+            .and_then(|index| sections.get(index));
+        let kind = match section.map(|section| section.kind) {
+            Some(SectionKind::Code) => SymbolKind::Function,
+            Some(SectionKind::ThreadLocal) => SymbolKind::Tls,
+            Some(_) => SymbolKind::Object,
+            None => SymbolKind::Other,
+        };
+        let binding = if nlist.n_type & N_EXT == 0 {
+            SymbolBinding::Local
+        } else if nlist.n_desc & N_WEAK_DEF != 0 {
+            SymbolBinding::Weak
+        } else {
+            SymbolBinding::Global
+        };
+        Symbol {
+            address: nlist.n_value.wrapping_add(offset),
+            size: 0,
+            kind,
+            binding,
+            visibility: SymbolVisibility::Default,
+            origin: SymbolOrigin::MachO,
+        }
+    }
+}
+
+/// Data that symbol names can be referenced in, like a memory mapped binary
+pub(crate) type NameSource = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// The symbols of a binary, indexed by name and by address.
+///
+/// Names read from a binary aren't copied: they are kept as references into the string
+/// tables of the memory mapped file, which the map holds on to. The indexes for looking up
+/// symbols by name and by address are built on the first lookup, and dropped whenever the
+/// symbols change.
+#[derive(Clone, Default)]
+pub struct SymbolMap {
+    sources: Vec<NameSource>,
+    // holds the names that aren't in any of the sources
+    arena: Vec<u8>,
+    // every symbol that was added, in order. Symbols that were replaced by a later symbol
+    // with the same name (or weren't added since the name existed already) are only dropped
+    // from here when the map is modified through `iter_mut`.
+    entries: Vec<Entry>,
+    by_name: OnceLock<NameIndex>,
+    by_address: OnceLock<Vec<AddressIndexEntry>>,
+    // demangled names to entries, only built for lookups by demangled name
+    by_demangled: OnceLock<HashMap<String, u32>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    name: NameRef,
+    symbol: Symbol,
+    // whether this entry replaces earlier entries with the same name (`insert`), or is only
+    // used if there aren't any (`insert_if_absent`)
+    replace: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct NameRef {
+    source: u32,
+    start: u32,
+    len: u32,
+}
+
+// the source index for names stored in the arena
+const ARENA: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
+struct NameIndex {
+    // open addressing hash table of (hash, entry) pairs, using linear probing
+    slots: Vec<(u32, u32)>,
+    // the number of bits of the hash used to pick the first slot to probe
+    bits: u32,
+    // the entries that are in the map, in the order they were added
+    entries: Vec<u32>,
+}
+
+const EMPTY_SLOT: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
+struct AddressIndexEntry {
+    start: u64,
+    end: u64,
+    // the largest end address of this entry and all entries before it, which bounds
+    // how far back a lookup needs to search for symbols that contain an address
+    max_end: u64,
+    entry: u32,
+}
+
+impl SymbolMap {
+    /// Creates an empty symbol map
+    pub fn new() -> SymbolMap {
+        SymbolMap::default()
+    }
+
+    /// Returns the named symbol, if it exists
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.find(name)
+            .map(|entry| &self.entries[entry as usize].symbol)
+    }
+
+    /// Returns the first of `names` that exists, along with its symbol. This is useful for
+    /// symbols that were renamed between versions, like `ruby_current_vm_ptr` and
+    /// `ruby_current_vm`.
+    pub fn get_first<'a>(&self, names: &[&'a str]) -> Option<(&'a str, &Symbol)> {
+        names
+            .iter()
+            .find_map(|&name| self.get(name).map(|symbol| (name, symbol)))
+    }
+
+    /// Iterates over the symbols whose names start with `prefix`, in the order they were added
+    pub fn with_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a Symbol)> + 'a {
+        self.iter()
+            .filter(move |(name, _)| name.starts_with(prefix))
+    }
+
+    /// Iterates over the symbols whose names match `regex`, in the order they were added
+    pub fn matching<'a>(
+        &'a self,
+        regex: &'a Regex,
+    ) -> impl Iterator<Item = (&'a str, &'a Symbol)> + 'a {
+        self.iter().filter(move |(name, _)| regex.is_match(name))
+    }
+
+    /// Returns the symbol whose demangled name (see [`demangle`]) is `name`, along with its
+    /// mangled name. If several symbols demangle to the same name, like C++ overloads, the
+    /// first one added is returned.
+    pub fn get_demangled(&self, name: &str) -> Option<(&str, &Symbol)> {
+        let index = self.by_demangled.get_or_init(|| {
+            let mut index = HashMap::new();
+            for &entry in self.name_index().entries.iter() {
+                if let Some(demangled) = demangle(self.name(entry)) {
+                    index.entry(demangled).or_insert(entry);
+                }
+            }
+            index
+        });
+        index
+            .get(name)
+            .map(|&entry| (self.name(entry), &self.entries[entry as usize].symbol))
+    }
+
+    fn find(&self, name: &str) -> Option<u32> {
+        let index = self.name_index();
+        let name = name.as_bytes();
+        let hash = hash_name(name);
+        let mask = index.slots.len() - 1;
+        let mut slot = (hash >> (64 - index.bits)) as usize;
+        loop {
+            let (slot_hash, entry) = index.slots[slot];
+            if entry == EMPTY_SLOT {
+                return None;
+            }
+            if slot_hash == hash as u32 && self.name_bytes(entry) == name {
+                return Some(entry);
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    /// Returns the address of the named symbol, if it exists
+    pub fn address(&self, name: &str) -> Option<u64> {
+        self.get(name).map(|symbol| symbol.address)
+    }
+
+    /// Returns `true` if the named symbol exists
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Adds a symbol, replacing any existing symbol with the same name
+    pub fn insert(&mut self, name: String, symbol: Symbol) {
+        let name = self.push_arena(&name);
+        self.push(name, symbol, true);
+    }
+
+    /// Adds a symbol, unless a symbol with the same name already exists
+    pub fn insert_if_absent(&mut self, name: &str, symbol: Symbol) {
+        let name = self.push_arena(name);
+        self.push(name, symbol, false);
+    }
+
+    /// Registers data that names passed to `insert_from_source` can point into, returning
+    /// the id to pass for them
+    pub(crate) fn add_source(&mut self, source: NameSource) -> u32 {
+        self.sources.push(source);
+        (self.sources.len() - 1) as u32
+    }
+
+    /// Adds a symbol whose name is a slice of a registered source, without copying the
+    /// name. `replace` has the same meaning as calling `insert` rather than
+    /// `insert_if_absent`.
+    pub(crate) fn insert_from_source(
+        &mut self,
+        source: u32,
+        name: &str,
+        symbol: Symbol,
+        replace: bool,
+    ) {
+        let name = self
+            .source_name(source, name)
+            .unwrap_or_else(|| self.push_arena(name));
+        self.push(name, symbol, replace);
+    }
+
+    /// Iterates over all of the symbols, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Symbol)> {
+        self.name_index()
+            .entries
+            .iter()
+            .map(move |&entry| (self.name(entry), &self.entries[entry as usize].symbol))
+    }
+
+    /// Iterates mutably over all of the symbols, in the order they were added
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Symbol)> {
+        self.compact();
+        self.by_address.take();
+        self.by_demangled.take();
+        let SymbolMap {
+            sources,
+            arena,
+            entries,
+            ..
+        } = self;
+        let (sources, arena) = (&*sources, &*arena);
+        entries.iter_mut().map(move |entry| {
+            let name = name_bytes(sources, arena, entry.name);
+            (
+                std::str::from_utf8(name).unwrap_or_default(),
+                &mut entry.symbol,
+            )
+        })
+    }
+
+    /// Finds the symbol containing `addr`, returning its name and the offset of `addr`
+    /// from the start of the symbol.
+    ///
+    /// Only functions and objects with a known size are considered. When symbols overlap,
+    /// the one starting closest to `addr` wins, and global symbols are preferred over aliases
+    /// with the same address.
+    pub fn symbolize(&self, addr: u64) -> Option<(&str, u64)> {
+        let index = self.by_address.get_or_init(|| self.build_address_index());
+        let end = index.partition_point(|entry| entry.start <= addr);
+        for entry in index[..end].iter().rev() {
+            if entry.max_end <= addr {
+                break;
+            }
+            if addr < entry.end {
+                return Some((self.name(entry.entry), addr - entry.start));
+            }
+        }
+        None
+    }
+
+    fn build_address_index(&self) -> Vec<AddressIndexEntry> {
+        let mut index: Vec<AddressIndexEntry> = self
+            .name_index()
+            .entries
+            .iter()
+            .filter_map(|&entry| {
+                let symbol = &self.entries[entry as usize].symbol;
+                let sized = symbol.size > 0
+                    && matches!(symbol.kind, SymbolKind::Function | SymbolKind::Object);
+                sized.then(|| AddressIndexEntry {
+                    start: symbol.address,
+                    end: symbol.address.saturating_add(symbol.size),
+                    max_end: 0,
+                    entry,
+                })
+            })
+            .collect();
+
+        // lookups walk backwards from the last entry starting at or before the address, so
+        // order the preferred symbol last among those starting at the same address
+        let rank = |entry: u32| match self.entries[entry as usize].symbol.binding {
+            SymbolBinding::Local => 0,
+            SymbolBinding::Weak => 1,
+            SymbolBinding::Global => 2,
+        };
+        index.sort_by(|a, b| {
+            (a.start, rank(a.entry), Reverse(self.name_bytes(a.entry))).cmp(&(
+                b.start,
+                rank(b.entry),
+                Reverse(self.name_bytes(b.entry)),
+            ))
+        });
+
+        let mut max_end = 0;
+        for entry in index.iter_mut() {
+            max_end = max_end.max(entry.end);
+            entry.max_end = max_end;
+        }
+        index
+    }
+
+    /// Returns the number of symbols
+    pub fn len(&self) -> usize {
+        self.name_index().entries.len()
+    }
+
+    /// Returns `true` if there are no symbols
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&mut self, name: NameRef, symbol: Symbol, replace: bool) {
+        self.by_name.take();
+        self.by_address.take();
+        self.by_demangled.take();
+        self.entries.push(Entry {
+            name,
+            symbol,
+            replace,
+        });
+    }
+
+    fn push_arena(&mut self, name: &str) -> NameRef {
+        let start = self.arena.len() as u32;
+        self.arena.extend_from_slice(name.as_bytes());
+        NameRef {
+            source: ARENA,
+            start,
+            len: name.len() as u32,
+        }
+    }
+
+    /// Returns a reference to `name` in the source, if it points into it
+    fn source_name(&self, source: u32, name: &str) -> Option<NameRef> {
+        let data = (*self.sources.get(source as usize)?).as_ref().as_ref();
+        let start = (name.as_ptr() as usize).checked_sub(data.as_ptr() as usize)?;
+        if start + name.len() > data.len() {
+            return None;
+        }
+        Some(NameRef {
+            source,
+            start: start.try_into().ok()?,
+            len: name.len().try_into().ok()?,
+        })
+    }
+
+    fn name_bytes(&self, entry: u32) -> &[u8] {
+        name_bytes(
+            &self.sources,
+            &self.arena,
+            self.entries[entry as usize].name,
+        )
+    }
+
+    fn name(&self, entry: u32) -> &str {
+        // names are only ever added from a &str
+        std::str::from_utf8(self.name_bytes(entry)).unwrap_or_default()
+    }
+
+    fn name_index(&self) -> &NameIndex {
+        self.by_name.get_or_init(|| self.build_name_index())
+    }
+
+    fn build_name_index(&self) -> NameIndex {
+        // keep the table at most half full
+        let capacity = (self.entries.len() * 2).next_power_of_two().max(16);
+        let bits = capacity.trailing_zeros();
+        let mut slots = vec![(0, EMPTY_SLOT); capacity];
+
+        // of the entries with the same name, the last one added with `insert` wins, or the
+        // first one if they were all added with `insert_if_absent`
+        for (index, entry) in self.entries.iter().enumerate() {
+            let name = name_bytes(&self.sources, &self.arena, entry.name);
+            let hash = hash_name(name);
+            let mut slot = (hash >> (64 - bits)) as usize;
+            loop {
+                let (slot_hash, slot_entry) = slots[slot];
+                if slot_entry == EMPTY_SLOT {
+                    slots[slot] = (hash as u32, index as u32);
+                    break;
+                }
+                if slot_hash == hash as u32 && self.name_bytes(slot_entry) == name {
+                    if entry.replace {
+                        slots[slot].1 = index as u32;
+                    }
+                    break;
+                }
+                slot = (slot + 1) & (capacity - 1);
+            }
+        }
+
+        let mut entries: Vec<u32> = slots
+            .iter()
+            .map(|&(_, entry)| entry)
+            .filter(|&entry| entry != EMPTY_SLOT)
+            .collect();
+        entries.sort_unstable();
+        NameIndex {
+            slots,
+            bits,
+            entries,
+        }
+    }
+
+    /// Drops the entries that were replaced
+    fn compact(&mut self) {
+        let index = match self.by_name.take() {
+            Some(index) => index,
+            None => self.build_name_index(),
+        };
+        if index.entries.len() == self.entries.len() {
+            let _ = self.by_name.set(index);
+            return;
+        }
+        let mut entries: Vec<Entry> = index
+            .entries
+            .iter()
+            .map(|&entry| self.entries[entry as usize].clone())
+            .collect();
+        // nothing added before this point can be replaced by `insert_if_absent`
+        for entry in entries.iter_mut() {
+            entry.replace = true;
+        }
+        self.entries = entries;
+    }
+}
+
+/// Hashes a symbol name a word at a time (like FxHash), which is much faster than the
+/// default SipHash for the long names of C++ and Rust symbols
+fn hash_name(name: &[u8]) -> u64 {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+    let mut hash = name.len() as u64;
+    let mut words = name.chunks_exact(8);
+    for word in &mut words {
+        let word = u64::from_le_bytes(word.try_into().unwrap());
+        hash = (hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+    let mut tail = [0; 8];
+    tail[..words.remainder().len()].copy_from_slice(words.remainder());
+    hash = (hash.rotate_left(5) ^ u64::from_le_bytes(tail)).wrapping_mul(SEED);
+    // the high bits pick the slot and the low bits are compared, so mix them together
+    hash ^ (hash >> 29)
+}
+
+/// Demangles an Itanium C++ or Rust (legacy or v0) symbol name, returning `None` for names
+/// that aren't mangled.
+///
+/// C++ names are demangled without their parameters and return type, and Rust names without
+/// their hash, so that `_ZN6python7Runtime8instanceEv` becomes `python::Runtime::instance`.
+pub fn demangle(name: &str) -> Option<String> {
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return Some(format!("{:#}", demangled));
+    }
+    if name.starts_with("_Z") {
+        let options = cpp_demangle::DemangleOptions::new()
+            .no_params()
+            .no_return_type();
+        return cpp_demangle::Symbol::new(name)
+            .ok()?
+            .demangle_with_options(&options)
+            .ok();
+    }
+    None
+}
+
+fn name_bytes<'a>(sources: &'a [NameSource], arena: &'a [u8], name: NameRef) -> &'a [u8] {
+    let data = if name.source == ARENA {
+        arena
+    } else {
+        (*sources[name.source as usize]).as_ref()
+    };
+    &data[name.start as usize..][..name.len as usize]
+}
+
+impl std::fmt::Debug for SymbolMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl Extend<(String, Symbol)> for SymbolMap {
+    fn extend<I: IntoIterator<Item = (String, Symbol)>>(&mut self, iter: I) {
+        for (name, symbol) in iter {
+            self.insert(name, symbol);
+        }
+    }
+}
+
+/// Fills in the sizes of functions and objects from formats that don't record them
+/// (Mach-O and PE), as the distance to the next symbol or the end of the section.
+pub(crate) fn infer_sizes(symbols: &mut SymbolMap, sections: &[Section]) {
+    let mut addresses: Vec<u64> = symbols.iter().map(|(_, symbol)| symbol.address).collect();
+    addresses.sort_unstable();
+    addresses.dedup();
+
+    for (_, symbol) in symbols.iter_mut() {
+        if symbol.size != 0 || !matches!(symbol.kind, SymbolKind::Function | SymbolKind::Object) {
+            continue;
+        }
+        let section_end = match sections
+            .iter()
+            .find(|section| section.contains(symbol.address))
+        {
+            Some(section) => section.range().end,
+            None => continue,
+        };
+        let next = addresses
+            .get(addresses.partition_point(|&addr| addr <= symbol.address))
+            .copied()
+            .unwrap_or(u64::MAX);
+        symbol.size = next.min(section_end) - symbol.address;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_map_replacement() {
+        let symbol = |address| Symbol::new(address, SymbolOrigin::Symtab);
+        let strtab: Arc<Vec<u8>> = Arc::new(b"\0alpha\0beta\0".to_vec());
+        let name =
+            |start: usize, len: usize| std::str::from_utf8(&strtab[start..start + len]).unwrap();
+
+        let mut symbols = SymbolMap::new();
+        assert!(symbols.is_empty());
+        let source = symbols.add_source(strtab.clone());
+        symbols.insert_from_source(source, name(7, 4), symbol(1), true);
+        symbols.insert_if_absent("beta", symbol(2));
+        symbols.insert_from_source(source, name(1, 5), symbol(3), false);
+        symbols.insert_if_absent("alpha", symbol(4));
+        symbols.insert("gamma".to_owned(), symbol(5));
+        symbols.insert("gamma".to_owned(), symbol(6));
+
+        assert_eq!(symbols.address("alpha"), Some(3));
+        assert_eq!(symbols.address("beta"), Some(1));
+        assert_eq!(symbols.address("gamma"), Some(6));
+        assert_eq!(symbols.address("delta"), None);
+        // replaced and skipped symbols aren't counted
+        assert_eq!(symbols.len(), 3);
+        assert!(!symbols.is_empty());
+        // names from the source aren't copied
+        assert_eq!(symbols.source_name(source, name(1, 5)).unwrap().start, 1);
+        assert!(symbols.source_name(source, "alpha").is_none());
+
+        for (_, symbol) in symbols.iter_mut() {
+            symbol.address += 10;
+        }
+        let names: Vec<_> = symbols
+            .iter()
+            .map(|(name, symbol)| (name, symbol.address))
+            .collect();
+        assert_eq!(names, vec![("beta", 11), ("alpha", 13), ("gamma", 16)]);
+
+        // existing symbols can still be replaced after the map was compacted
+        symbols.insert_if_absent("alpha", symbol(7));
+        symbols.insert("beta".to_owned(), symbol(8));
+        assert_eq!(symbols.address("alpha"), Some(13));
+        assert_eq!(symbols.address("beta"), Some(8));
+    }
+
+    #[test]
+    fn test_symbol_map_queries() {
+        let mut symbols = SymbolMap::new();
+        for (i, name) in [
+            "ruby_current_vm_ptr",
+            "ruby_version",
+            "_ZN6python7Runtime8instanceEv",
+            "_ZN6python7Runtime8instanceEi",
+            "_ZN4core3fmt5write17h0123456789abcdefE",
+            "_RNvCs1234_7mycrate3foo",
+        ]
+        .iter()
+        .enumerate()
+        {
+            symbols.insert(
+                name.to_string(),
+                Symbol::new(i as u64, SymbolOrigin::Symtab),
+            );
+        }
+
+        let (name, symbol) = symbols
+            .get_first(&["ruby_current_vm", "ruby_current_vm_ptr"])
+            .unwrap();
+        assert_eq!((name, symbol.address), ("ruby_current_vm_ptr", 0));
+        assert!(symbols.get_first(&["ruby_current_vm"]).is_none());
+
+        let names: Vec<_> = symbols.with_prefix("ruby_").map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["ruby_current_vm_ptr", "ruby_version"]);
+        let regex = Regex::new(r"^ruby_current_(vm|vm_ptr)$").unwrap();
+        assert_eq!(symbols.matching(&regex).count(), 1);
+
+        // overloads demangle to the same name, and the first one wins
+        let (name, symbol) = symbols.get_demangled("python::Runtime::instance").unwrap();
+        assert_eq!((name, symbol.address), ("_ZN6python7Runtime8instanceEv", 2));
+        assert_eq!(
+            symbols.get_demangled("core::fmt::write").unwrap().1.address,
+            4
+        );
+        assert_eq!(symbols.get_demangled("mycrate::foo").unwrap().1.address, 5);
+        assert!(symbols.get_demangled("ruby_version").is_none());
+        assert_eq!(demangle("ruby_version"), None);
+    }
+}
@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+
+use log::*;
+
+use crate::binary_parser::Architecture;
+use crate::procfs::Procfs;
+
+/// Describes the process that binaries were loaded into, for [`parse_binary`].
+///
+/// Parsing only depends on this and on the format of the binary, rather than on the OS that
+/// spytools was compiled for, so ELF, Mach-O and PE binaries give the same results on every
+/// host. This makes it possible to inspect binaries copied from another machine.
+///
+/// [`parse_binary`]: crate::binary_parser::parse_binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    /// The CPU architecture of the process, which picks the slice to parse from fat Mach-O
+    /// binaries. When this isn't set, the architecture spytools was compiled for is preferred.
+    pub arch: Option<Architecture>,
+    /// Where the files of the process are read from
+    pub files: FileSource,
+    /// Directories to search for separate debug files, in addition to the default locations
+    /// (the binary's directory, its `.debug/` subdirectory and `/usr/lib/debug`).
+    ///
+    /// Debug files are looked up both directly in each directory and in the directory joined
+    /// with the directory of the binary, mirroring how gdb treats its `debug-file-directory`
+    /// setting. Build-id based lookups use `<dir>/.build-id/xx/yyyy.debug`.
+    pub debug_search_paths: Vec<PathBuf>,
+}
+
+/// Where the binaries and debug files of a process are read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileSource {
+    /// Through the proc filesystem directory of a running Linux process (`/proc/<pid>`): the
+    /// main binary is read from `/proc/<pid>/exe` and other files from `/proc/<pid>/root`,
+    /// which works when the process is in a container or the binary was deleted
+    Procfs(Procfs, remoteprocess::Pid),
+    /// From the paths as seen by the process, on this host
+    Host,
+    /// Relative to a root directory, like a copy of another machine's filesystem
+    Root(PathBuf),
+}
+
+impl Target {
+    /// Returns the target for a process running on this host
+    pub fn process(pid: remoteprocess::Pid) -> Target {
+        if cfg!(target_os = "linux") {
+            Target::process_in(&Procfs::default(), pid)
+        } else {
+            Target::host()
+        }
+    }
+
+    /// Returns the target for a process whose files are read through the proc filesystem
+    /// at `procfs`
+    pub fn process_in(procfs: &Procfs, pid: remoteprocess::Pid) -> Target {
+        Target {
+            arch: None,
+            files: FileSource::Procfs(procfs.clone(), pid),
+            debug_search_paths: Vec::new(),
+        }
+    }
+
+    /// Returns a target whose files are read from the paths as given, on this host
+    pub fn host() -> Target {
+        Target {
+            arch: None,
+            files: FileSource::Host,
+            debug_search_paths: Vec::new(),
+        }
+    }
+
+    /// Returns a target whose files are read relative to `root`
+    pub fn root<P: Into<PathBuf>>(root: P) -> Target {
+        Target {
+            arch: None,
+            files: FileSource::Root(root.into()),
+            debug_search_paths: Vec::new(),
+        }
+    }
+
+    /// Sets the CPU architecture of the process
+    pub fn with_arch(mut self, arch: Option<Architecture>) -> Target {
+        self.arch = arch;
+        self
+    }
+
+    /// Adds a directory to search for separate debug files (see
+    /// [`debug_search_paths`](Target::debug_search_paths))
+    pub fn with_debug_search_path<P: Into<PathBuf>>(mut self, path: P) -> Target {
+        let path = path.into();
+        if !self.debug_search_paths.contains(&path) {
+            self.debug_search_paths.push(path);
+        }
+        self
+    }
+
+    /// Returns the path to read a binary of the process from, given its path as seen by the
+    /// process
+    pub fn binary_path(&self, filename: &Path, is_bin: bool) -> PathBuf {
+        match &self.files {
+            FileSource::Procfs(procfs, pid) if is_bin => procfs.process_path(*pid, "exe"),
+            FileSource::Host => filename.to_owned(),
+            _ => join_root(&self.root_dir(), filename),
+        }
+    }
+
+    /// Returns the path to read a file mapped into the process from, along with whether that
+    /// is the file the process mapped. `filename` is the path from the memory maps, which can
+    /// end in ` (deleted)`, and `addr` and `size` give one of the memory maps of the file.
+    ///
+    /// When the file was deleted or replaced after it was mapped (like by a deploy), or never
+    /// was on disk (like `memfd:` files), it's read through `/proc/<pid>/map_files` instead.
+    pub(crate) fn mapped_file(
+        &self,
+        filename: &Path,
+        is_bin: bool,
+        addr: u64,
+        size: u64,
+    ) -> (PathBuf, FileStatus) {
+        let (filename, deleted) = strip_deleted(filename);
+        let path = self.binary_path(filename, is_bin);
+        let (procfs, pid) = match &self.files {
+            FileSource::Procfs(procfs, pid) => (procfs, *pid),
+            _ => return (path, FileStatus::Unknown),
+        };
+
+        // the executable can always be read through /proc/<pid>/exe
+        let mapped = if is_bin {
+            path.clone()
+        } else {
+            procfs.process_path(pid, &format!("map_files/{:x}-{:x}", addr, addr + size))
+        };
+        let on_disk = join_root(&self.root_dir(), filename);
+        match (file_id(&mapped), file_id(&on_disk)) {
+            (Some(mapped_id), Some(disk_id)) if mapped_id == disk_id => (path, FileStatus::Current),
+            (Some(_), Some(_)) => {
+                info!(
+                    "{} was replaced since it was mapped, reading {}",
+                    filename.display(),
+                    mapped.display()
+                );
+                (mapped, FileStatus::Replaced)
+            }
+            (Some(_), None) => {
+                info!(
+                    "{} was deleted since it was mapped, reading {}",
+                    filename.display(),
+                    mapped.display()
+                );
+                (mapped, FileStatus::Deleted)
+            }
+            (None, _) if deleted => {
+                warn!(
+                    "{} was deleted and {} can't be read, the file at its path might not be the mapped one",
+                    filename.display(),
+                    mapped.display()
+                );
+                (path, FileStatus::Deleted)
+            }
+            (None, _) => (path, FileStatus::Unknown),
+        }
+    }
+
+    /// Returns the directory that paths in the process are relative to
+    pub fn root_dir(&self) -> PathBuf {
+        match &self.files {
+            FileSource::Procfs(procfs, pid) => procfs.process_path(*pid, "root"),
+            FileSource::Host => PathBuf::from("/"),
+            FileSource::Root(root) => root.clone(),
+        }
+    }
+}
+
+/// Whether the file that a binary was read from is the file that is mapped into the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The file at the binary's path is the one the process mapped
+    Current,
+    /// The file at the binary's path was replaced after the process mapped it, so the mapped
+    /// file was read through `/proc/<pid>/map_files` instead
+    Replaced,
+    /// The mapped file was deleted, or never had a path (like `memfd:` files). It was read
+    /// through `/proc/<pid>/map_files` when that was possible.
+    Deleted,
+    /// It wasn't possible to check, like for files read from a copy of another machine's
+    /// filesystem
+    Unknown,
+}
+
+/// Removes the ` (deleted)` suffix that Linux adds to the paths of deleted files in
+/// `/proc/<pid>/maps` and `/proc/<pid>/exe`, returning whether it was there
+pub(crate) fn strip_deleted(path: &Path) -> (&Path, bool) {
+    match path
+        .to_str()
+        .and_then(|path| path.strip_suffix(" (deleted)"))
+    {
+        Some(path) => (Path::new(path), true),
+        None => (path, false),
+    }
+}
+
+/// Joins an absolute path as seen by the process to the root directory it's relative to
+pub(crate) fn join_root(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+// the device and inode of a file, which identify it even once it's deleted or renamed
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_paths() {
+        let target = Target::process_in(&Procfs::default(), 1234);
+        let path = Path::new("/usr/lib/libpython3.11.so.1.0");
+        assert_eq!(
+            target.binary_path(path, false),
+            Path::new("/proc/1234/root/usr/lib/libpython3.11.so.1.0")
+        );
+        assert_eq!(
+            target.binary_path(Path::new("/usr/bin/python3"), true),
+            Path::new("/proc/1234/exe")
+        );
+
+        let target = Target::root("/tmp/sysroot");
+        assert_eq!(
+            target.binary_path(path, false),
+            Path::new("/tmp/sysroot/usr/lib/libpython3.11.so.1.0")
+        );
+        assert_eq!(target.root_dir(), Path::new("/tmp/sysroot"));
+
+        assert_eq!(Target::host().binary_path(path, true), path);
+
+        assert_eq!(
+            strip_deleted(Path::new("/usr/lib/libruby.so.3.2 (deleted)")),
+            (Path::new("/usr/lib/libruby.so.3.2"), true)
+        );
+        assert_eq!(strip_deleted(path), (path, false));
+    }
+}
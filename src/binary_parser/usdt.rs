@@ -0,0 +1,140 @@
+use std::convert::TryInto;
+
+use goblin::elf::Elf;
+use log::*;
+
+/// A USDT (user space statically defined tracing) probe, like the dtrace probes that CPython
+/// and CRuby can be built with. These are described by `NT_STAPSDT` notes in the
+/// `.note.stapsdt` section, which is how `<sys/sdt.h>` records them on Linux.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsdtProbe {
+    /// The provider of the probe, like `python` or `ruby`
+    pub provider: String,
+    /// The name of the probe, like `function__entry`
+    pub name: String,
+    /// The address of the probe's instruction in the process's memory, to attach a uprobe to
+    pub address: u64,
+    /// The address of the probe's semaphore in the process's memory, if it has one. This is
+    /// a 16 bit counter, and the runtime only fires the probe while it is non-zero.
+    pub semaphore: Option<u64>,
+    /// The locations of the probe's arguments, in the `size@operand` format of the assembler
+    /// (like `-4@%edi` or `8@-16(%rbp)`). A negative size means the argument is signed.
+    pub arguments: Vec<String>,
+}
+
+const NT_STAPSDT: u32 = 3;
+
+/// Reads the USDT probes from the `.note.stapsdt` section of a binary, relocating their
+/// addresses by `load_bias`
+pub(crate) fn elf_probes(elf: &Elf, buffer: &[u8], load_bias: u64) -> Vec<UsdtProbe> {
+    let notes = match elf.iter_note_sections(buffer, Some(".note.stapsdt")) {
+        Some(notes) => notes,
+        None => return Vec::new(),
+    };
+
+    // the notes record the link time address of .stapsdt.base, so that addresses can be
+    // corrected when the binary was moved afterwards without updating them (by prelink)
+    let base_addr = elf
+        .section_headers
+        .iter()
+        .find(|header| elf.shdr_strtab.get_at(header.sh_name) == Some(".stapsdt.base"))
+        .map(|header| header.sh_addr);
+
+    let mut probes = Vec::new();
+    for note in notes {
+        let note = match note {
+            Ok(note) => note,
+            Err(e) => {
+                warn!("Failed to read .note.stapsdt: {}", e);
+                break;
+            }
+        };
+        if note.n_type != NT_STAPSDT || note.name != "stapsdt" {
+            continue;
+        }
+        match parse_probe(note.desc, elf.is_64, elf.little_endian, base_addr) {
+            Some(mut probe) => {
+                probe.address = probe.address.wrapping_add(load_bias);
+                probe.semaphore = probe.semaphore.map(|addr| addr.wrapping_add(load_bias));
+                probes.push(probe);
+            }
+            None => warn!("Failed to parse USDT probe note"),
+        }
+    }
+    probes
+}
+
+/// Parses the description of a `NT_STAPSDT` note: the probe address, the address of
+/// `.stapsdt.base` and the semaphore address (all the size of a pointer), followed by the
+/// provider, name and arguments as NUL terminated strings
+fn parse_probe(
+    desc: &[u8],
+    is_64: bool,
+    little_endian: bool,
+    base_addr: Option<u64>,
+) -> Option<UsdtProbe> {
+    let word_size = if is_64 { 8 } else { 4 };
+    let word = |index: usize| -> Option<u64> {
+        let bytes = desc.get(index * word_size..(index + 1) * word_size)?;
+        Some(match (is_64, little_endian) {
+            (true, true) => u64::from_le_bytes(bytes.try_into().ok()?),
+            (true, false) => u64::from_be_bytes(bytes.try_into().ok()?),
+            (false, true) => u64::from(u32::from_le_bytes(bytes.try_into().ok()?)),
+            (false, false) => u64::from(u32::from_be_bytes(bytes.try_into().ok()?)),
+        })
+    };
+    let (mut address, note_base, mut semaphore) = (word(0)?, word(1)?, word(2)?);
+    if let Some(base_addr) = base_addr {
+        let adjustment = base_addr.wrapping_sub(note_base);
+        address = address.wrapping_add(adjustment);
+        if semaphore != 0 {
+            semaphore = semaphore.wrapping_add(adjustment);
+        }
+    }
+
+    let mut strings = desc[3 * word_size..].split(|&b| b == 0);
+    let mut next_string = || std::str::from_utf8(strings.next()?).ok();
+    let provider = next_string()?.to_owned();
+    let name = next_string()?.to_owned();
+    let arguments = next_string()
+        .unwrap_or("")
+        .split_whitespace()
+        .map(|argument| argument.to_owned())
+        .collect();
+
+    Some(UsdtProbe {
+        provider,
+        name,
+        address,
+        semaphore: if semaphore != 0 {
+            Some(semaphore)
+        } else {
+            None
+        },
+        arguments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_prelinked() {
+        // an ELF32 probe from a binary moved by 0x10000 after the note was written
+        let mut desc = Vec::new();
+        for word in [0x1100u32, 0x2000, 0x4008] {
+            desc.extend_from_slice(&word.to_le_bytes());
+        }
+        desc.extend_from_slice(b"python\0function__entry\0-4@8(%esp) 4@%eax\0");
+
+        let probe = parse_probe(&desc, false, true, Some(0x12000)).unwrap();
+        assert_eq!(probe.provider, "python");
+        assert_eq!(probe.name, "function__entry");
+        assert_eq!(probe.address, 0x11100);
+        assert_eq!(probe.semaphore, Some(0x14008));
+        assert_eq!(probe.arguments, vec!["-4@8(%esp)", "4@%eax"]);
+
+        assert!(parse_probe(&desc[..8], false, true, None).is_none());
+    }
+}
@@ -6,5 +6,8 @@
 pub mod binary_parser;
 /// For inspecting a process's symbols and handling Python-and Ruby-specific details.
 pub mod process;
+/// For locating the files that describe processes in the proc filesystem
+pub mod procfs;
 
 pub use process::process_info::ProcessInfo;
+pub use procfs::Procfs;
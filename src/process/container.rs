@@ -0,0 +1,242 @@
+use lazy_static::lazy_static;
+use log::*;
+use regex::Regex;
+
+use crate::procfs::Procfs;
+
+lazy_static! {
+    // a 64 character container id, on its own (cgroupfs driver) or in a systemd scope
+    static ref CONTAINER_ID: Regex = Regex::new(
+        r"^(?:(docker|cri-containerd|crio|libpod)-)?([0-9a-f]{64})(?:\.scope)?$"
+    )
+    .unwrap();
+    // the pod uid in kubepods cgroups, where the systemd driver uses _ instead of -
+    static ref POD_UID: Regex = Regex::new(
+        r"pod([0-9a-f]{8}[-_][0-9a-f]{4}[-_][0-9a-f]{4}[-_][0-9a-f]{4}[-_][0-9a-f]{12})(?:\.slice)?$"
+    )
+    .unwrap();
+}
+
+/// The container runtime that started a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerRuntime {
+    /// Docker (or Moby)
+    Docker,
+    /// containerd, through its CRI plugin
+    Containerd,
+    /// CRI-O
+    CriO,
+    /// Podman
+    Podman,
+}
+
+/// The container and namespaces that a process runs in, relative to spytools
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerInfo {
+    /// Whether the process is in another mount namespace than spytools, in which case its
+    /// files are read through `/proc/<pid>/root`
+    pub other_mount_namespace: bool,
+    /// Whether the process is in another pid namespace than spytools
+    pub other_pid_namespace: bool,
+    /// The pid of the process in its own pid namespace (the last `NSpid` entry of
+    /// `/proc/<pid>/status`), which is what the process sees from `getpid`
+    pub namespace_pid: Option<remoteprocess::Pid>,
+    /// The cgroup of the process, from `/proc/<pid>/cgroup`. This is the cgroup that names
+    /// the container if there is one, and otherwise the cgroup v2 path.
+    pub cgroup: Option<String>,
+    /// The id of the container that the process runs in
+    pub container_id: Option<String>,
+    /// The runtime of the container, where it can be told from the cgroup
+    pub runtime: Option<ContainerRuntime>,
+    /// The uid of the Kubernetes pod that the container belongs to
+    pub pod_uid: Option<String>,
+}
+
+impl ContainerInfo {
+    /// Reads the container and namespaces of a running process from the proc filesystem.
+    /// Anything that can't be read (like the namespaces of processes owned by another user)
+    /// is left unset.
+    pub fn for_process(procfs: &Procfs, pid: remoteprocess::Pid) -> ContainerInfo {
+        let namespace_differs = |name: &str| {
+            let namespace = format!("ns/{}", name);
+            let own = std::fs::read_link(procfs.self_path(&namespace));
+            let target = std::fs::read_link(procfs.process_path(pid, &namespace));
+            match (own, target) {
+                (Ok(own), Ok(target)) => own != target,
+                (_, Err(e)) | (Err(e), _) => {
+                    debug!("Failed to read {} namespace of {}: {}", name, pid, e);
+                    false
+                }
+            }
+        };
+
+        let mut info = match std::fs::read_to_string(procfs.process_path(pid, "cgroup")) {
+            Ok(cgroup) => ContainerInfo::from_cgroup(&cgroup),
+            Err(e) => {
+                debug!("Failed to read cgroup of {}: {}", pid, e);
+                ContainerInfo::default()
+            }
+        };
+        info.other_mount_namespace = namespace_differs("mnt");
+        info.other_pid_namespace = namespace_differs("pid");
+        info.namespace_pid = std::fs::read_to_string(procfs.process_path(pid, "status"))
+            .ok()
+            .and_then(|status| namespace_pid(&status));
+        info
+    }
+
+    /// Parses the contents of `/proc/<pid>/cgroup`, which has a `id:controllers:path` line for
+    /// each cgroup v1 hierarchy and a `0::path` line for the cgroup v2 hierarchy
+    pub fn from_cgroup(contents: &str) -> ContainerInfo {
+        let mut info = ContainerInfo::default();
+        let mut unified = None;
+        for line in contents.lines() {
+            let mut fields = line.splitn(3, ':');
+            let (id, controllers, path) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(id), Some(controllers), Some(path)) => (id, controllers, path),
+                _ => continue,
+            };
+            if id == "0" && controllers.is_empty() {
+                unified = Some(path);
+            }
+
+            if info.container_id.is_none() {
+                if let Some((container_id, runtime)) = container_id(path) {
+                    info.cgroup = Some(path.to_owned());
+                    info.container_id = Some(container_id);
+                    info.runtime = runtime;
+                }
+            }
+            if info.pod_uid.is_none() {
+                info.pod_uid = path
+                    .split('/')
+                    .find_map(|component| POD_UID.captures(component))
+                    .map(|captures| captures[1].replace('_', "-"));
+            }
+        }
+        if info.cgroup.is_none() {
+            info.cgroup = unified
+                .or_else(|| contents.lines().next()?.splitn(3, ':').nth(2))
+                .map(|path| path.to_owned());
+        }
+        info
+    }
+
+    /// Returns `true` if the process looks like it runs in a container
+    pub fn is_containerized(&self) -> bool {
+        self.other_mount_namespace || self.container_id.is_some()
+    }
+}
+
+// finds the innermost container id in a cgroup path, along with the runtime if it's known
+fn container_id(path: &str) -> Option<(String, Option<ContainerRuntime>)> {
+    let components: Vec<&str> = path.split('/').collect();
+    components
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(index, component)| {
+            let captures = CONTAINER_ID.captures(component)?;
+            let runtime = match captures.get(1).map(|prefix| prefix.as_str()) {
+                Some("docker") => Some(ContainerRuntime::Docker),
+                Some("cri-containerd") => Some(ContainerRuntime::Containerd),
+                Some("crio") => Some(ContainerRuntime::CriO),
+                Some("libpod") => Some(ContainerRuntime::Podman),
+                // with the cgroupfs driver, docker puts containers in /docker/<id>, while
+                // kubernetes doesn't tell which runtime it's using
+                _ if index > 0 && components[index - 1] == "docker" => {
+                    Some(ContainerRuntime::Docker)
+                }
+                _ => None,
+            };
+            Some((captures[2].to_owned(), runtime))
+        })
+}
+
+// the pid of the process in its own namespace, from the NSpid line of /proc/<pid>/status
+fn namespace_pid(status: &str) -> Option<remoteprocess::Pid> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("NSpid:"))?
+        .split_whitespace()
+        .last()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_parser::tests::fixture;
+
+    const ID: &str = "3f2b1c0d9e8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c";
+    const POD: &str = "0f1e2d3c-4b5a-6978-8a9b-0c1d2e3f4a5b";
+
+    fn cgroup(name: &str) -> ContainerInfo {
+        let path = fixture("cgroup").join(name);
+        ContainerInfo::from_cgroup(&std::fs::read_to_string(path).unwrap())
+    }
+
+    #[test]
+    fn test_container_from_cgroup() {
+        let expected = [
+            ("docker_v1", Some(ContainerRuntime::Docker), None),
+            ("docker_v2", Some(ContainerRuntime::Docker), None),
+            ("kubernetes_containerd_v1", None, Some(POD)),
+            (
+                "kubernetes_containerd_v2",
+                Some(ContainerRuntime::Containerd),
+                Some(POD),
+            ),
+            (
+                "kubernetes_crio_v2",
+                Some(ContainerRuntime::CriO),
+                Some(POD),
+            ),
+            ("podman_v2", Some(ContainerRuntime::Podman), None),
+        ];
+        for (name, runtime, pod_uid) in expected.iter() {
+            let info = cgroup(name);
+            assert_eq!(info.container_id.as_deref(), Some(ID), "{}", name);
+            assert_eq!(info.runtime, *runtime, "{}", name);
+            assert_eq!(info.pod_uid.as_deref(), *pod_uid, "{}", name);
+            assert!(info.cgroup.unwrap().contains(ID), "{}", name);
+        }
+        assert_eq!(
+            cgroup("docker_v1").cgroup.unwrap(),
+            format!("/docker/{}", ID)
+        );
+
+        for (name, path) in [
+            ("host_v1", "/user.slice/user-1000.slice/session-2.scope"),
+            ("host_v2", "/init.scope"),
+        ]
+        .iter()
+        {
+            let info = cgroup(name);
+            assert_eq!(info.container_id, None, "{}", name);
+            assert_eq!(info.pod_uid, None, "{}", name);
+            assert_eq!(info.cgroup.as_deref(), Some(*path), "{}", name);
+            assert!(!info.is_containerized());
+        }
+    }
+
+    #[test]
+    fn test_namespace_pid() {
+        let status = "Name:\tpython3\nPid:\t48213\nNSpid:\t48213\t7\nNSpgid:\t48213\t7\n";
+        assert_eq!(namespace_pid(status), Some(7));
+        assert_eq!(namespace_pid("NSpid:\t1234\n"), Some(1234));
+        assert_eq!(namespace_pid("Name:\tsleep\n"), None);
+
+        let info = ContainerInfo::for_process(
+            &Procfs::default(),
+            std::process::id() as remoteprocess::Pid,
+        );
+        assert!(!info.other_mount_namespace);
+        assert!(!info.other_pid_namespace);
+        assert_eq!(
+            info.namespace_pid,
+            Some(std::process::id() as remoteprocess::Pid)
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use anyhow::{format_err, Error};
+use log::*;
+use remoteprocess::ProcessMemory;
+
+use crate::binary_parser::{BinaryInfo, Class, Endianness};
+
+/// A module loaded by the dynamic linker, read from its `struct link_map` in the process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkMapEntry {
+    /// The path the dynamic linker loaded the module from (`l_name`). This is empty for the
+    /// executable, and can be a name like `linux-vdso.so.1` for modules that aren't files.
+    pub name: String,
+    /// The load bias of the module (`l_addr`), which is what the dynamic linker adds to the
+    /// addresses in the module's file
+    pub load_bias: u64,
+    /// The address of the module's dynamic section in the process's memory (`l_ld`)
+    pub dynamic: u64,
+    /// The linker namespace the module was loaded into, which is 0 apart from modules loaded
+    /// with `dlmopen`
+    pub namespace: usize,
+    /// The address of the `link_map` entry itself
+    pub address: u64,
+}
+
+const DT_NULL: u64 = 0;
+const DT_DEBUG: u64 = 21;
+
+// guards against walking a corrupt or cyclic list forever
+const MAX_ENTRIES: usize = 16384;
+const MAX_NAMESPACES: usize = 256;
+const MAX_NAME_LENGTH: usize = 4096;
+
+/// Finds the dynamic linker's `struct r_debug` in a process from the `DT_DEBUG` entry of the
+/// executable's dynamic segment (`PT_DYNAMIC`), which the dynamic linker points at it on
+/// startup.
+///
+/// This isn't set for programs run by invoking `ld.so` directly, where the `_r_debug` symbol
+/// of `ld.so` can be used instead.
+pub fn find_r_debug<P: ProcessMemory>(process: &P, binary: &BinaryInfo) -> Option<u64> {
+    let r_debug = binary.elf_dynamic().and_then(|(addr, size)| {
+        let reader = Reader::new(process, binary.class, binary.endianness);
+        let word_size = binary.class.pointer_size() as u64;
+        let count = size / (2 * word_size);
+        (0..count)
+            .map(|index| addr + index * 2 * word_size)
+            .map(|entry| Some((reader.word(entry)?, reader.word(entry + word_size)?)))
+            .take_while(|entry| !matches!(entry, None | Some((DT_NULL, _))))
+            .flatten()
+            .find(|&(tag, value)| tag == DT_DEBUG && value != 0)
+            .map(|(_, value)| value)
+    });
+    if r_debug.is_none() {
+        debug!("No DT_DEBUG entry in {}", binary.filename.display());
+    }
+    r_debug
+}
+
+/// Reads the modules loaded by the dynamic linker, by walking the `link_map` list of the
+/// `struct r_debug` at `r_debug` in the process (see [`find_r_debug`]).
+///
+/// The modules are returned in load order, which is the order that symbols are resolved in.
+/// With glibc 2.35 and later, the modules of the other linker namespaces (from `dlmopen`) are
+/// read as well, following the namespace list of `struct r_debug_extended`.
+pub fn read_link_map<P: ProcessMemory>(
+    process: &P,
+    r_debug: u64,
+    class: Class,
+    endianness: Endianness,
+) -> Result<Vec<LinkMapEntry>, Error> {
+    let reader = Reader::new(process, class, endianness);
+    let word_size = class.pointer_size() as u64;
+
+    let mut entries = Vec::new();
+    let mut r_debug = r_debug;
+    for namespace in 0..MAX_NAMESPACES {
+        // struct r_debug_extended { int r_version; struct link_map *r_map; ElfW(Addr) r_brk;
+        //     r_state; ElfW(Addr) r_ldbase; struct r_debug_extended *r_next; }
+        // where the int and the enum are padded to the size of a pointer
+        let version = reader
+            .u32(r_debug)
+            .ok_or_else(|| format_err!("Failed to read r_debug at {:#x}", r_debug))?;
+        let mut link_map = reader
+            .word(r_debug + word_size)
+            .ok_or_else(|| format_err!("Failed to read r_map at {:#x}", r_debug))?;
+
+        while link_map != 0 {
+            if entries.len() >= MAX_ENTRIES {
+                return Err(format_err!(
+                    "Found more than {} link_map entries, the list is likely corrupt",
+                    MAX_ENTRIES
+                ));
+            }
+            // struct link_map { ElfW(Addr) l_addr; char *l_name; ElfW(Dyn) *l_ld;
+            //     struct link_map *l_next, *l_prev; }
+            let field = |index: u64| {
+                reader
+                    .word(link_map + index * word_size)
+                    .ok_or_else(|| format_err!("Failed to read link_map entry at {:#x}", link_map))
+            };
+            let name_addr = field(1)?;
+            let name = match name_addr {
+                0 => String::new(),
+                addr => reader.c_string(addr).unwrap_or_else(|| {
+                    warn!("Failed to read l_name of link_map entry at {:#x}", link_map);
+                    String::new()
+                }),
+            };
+            entries.push(LinkMapEntry {
+                name,
+                load_bias: field(0)?,
+                dynamic: field(2)?,
+                namespace,
+                address: link_map,
+            });
+            link_map = field(3)?;
+        }
+
+        if version < 2 {
+            break;
+        }
+        r_debug = match reader.word(r_debug + 5 * word_size) {
+            Some(0) | None => break,
+            Some(next) => next,
+        };
+    }
+    Ok(entries)
+}
+
+// reads words of the target's size and byte order from the process
+struct Reader<'a, P> {
+    process: &'a P,
+    class: Class,
+    endianness: Endianness,
+}
+
+impl<'a, P: ProcessMemory> Reader<'a, P> {
+    fn new(process: &'a P, class: Class, endianness: Endianness) -> Self {
+        Reader {
+            process,
+            class,
+            endianness,
+        }
+    }
+
+    fn u32(&self, addr: u64) -> Option<u32> {
+        let mut buf = [0; 4];
+        self.process.read(addr as usize, &mut buf).ok()?;
+        Some(match self.endianness {
+            Endianness::Little => u32::from_le_bytes(buf),
+            Endianness::Big => u32::from_be_bytes(buf),
+        })
+    }
+
+    fn word(&self, addr: u64) -> Option<u64> {
+        match self.class {
+            Class::Bits32 => self.u32(addr).map(u64::from),
+            Class::Bits64 => {
+                let mut buf = [0; 8];
+                self.process.read(addr as usize, &mut buf).ok()?;
+                Some(match self.endianness {
+                    Endianness::Little => u64::from_le_bytes(buf),
+                    Endianness::Big => u64::from_be_bytes(buf),
+                })
+            }
+        }
+    }
+
+    // reads a NUL terminated string, without reading past the end of the page that the
+    // terminator is on
+    fn c_string(&self, addr: u64) -> Option<String> {
+        let mut bytes = Vec::new();
+        let mut addr = addr;
+        while bytes.len() < MAX_NAME_LENGTH {
+            let page_end = (addr | 0xfff) + 1;
+            let mut chunk = vec![0; (page_end - addr).min(256) as usize];
+            self.process.read(addr as usize, &mut chunk).ok()?;
+            if let Some(end) = chunk.iter().position(|&b| b == 0) {
+                bytes.extend_from_slice(&chunk[..end]);
+                return Some(String::from_utf8_lossy(&bytes).into_owned());
+            }
+            addr += chunk.len() as u64;
+            bytes.extend_from_slice(&chunk);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the memory of a fake process, starting at `base`
+    struct Memory {
+        base: u64,
+        data: Vec<u8>,
+    }
+
+    impl ProcessMemory for Memory {
+        fn read(&self, addr: usize, buf: &mut [u8]) -> Result<(), remoteprocess::Error> {
+            let start = (addr as u64)
+                .checked_sub(self.base)
+                .map(|start| start as usize)
+                .filter(|start| start + buf.len() <= self.data.len())
+                .ok_or_else(|| remoteprocess::Error::Other(format!("bad read {:#x}", addr)))?;
+            buf.copy_from_slice(&self.data[start..start + buf.len()]);
+            Ok(())
+        }
+    }
+
+    impl Memory {
+        fn put(&mut self, addr: u64, bytes: &[u8]) {
+            let start = (addr - self.base) as usize;
+            self.data[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+
+        fn put_words32(&mut self, addr: u64, words: &[u32]) {
+            let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+            self.put(addr, &bytes);
+        }
+
+        fn put_words64(&mut self, addr: u64, words: &[u64]) {
+            let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+            self.put(addr, &bytes);
+        }
+    }
+
+    #[test]
+    fn test_find_r_debug() {
+        use crate::binary_parser::tests::{parse_fixture, BASE};
+
+        // the dynamic segment of the fixture is at 0x3dd8, as DT_NEEDED, DT_DEBUG, DT_NULL
+        let mut binary = parse_fixture("libfixture.stripped");
+        let mut memory = Memory {
+            base: BASE,
+            data: vec![0; 0x5000],
+        };
+        memory.put_words64(BASE + 0x3dd8, &[1, 0x10, 21, 0x7f0000001000, 0, 0]);
+        assert_eq!(find_r_debug(&memory, &binary), Some(0x7f0000001000));
+
+        // the dynamic segment is found from the program headers, which can't be stripped
+        binary.sections.clear();
+        assert_eq!(find_r_debug(&memory, &binary), Some(0x7f0000001000));
+
+        // DT_DEBUG is only set once the dynamic linker has run
+        memory.put_words64(BASE + 0x3dd8, &[1, 0x10, 21, 0, 0, 0]);
+        assert_eq!(find_r_debug(&memory, &binary), None);
+    }
+
+    #[test]
+    fn test_read_link_map_namespaces() {
+        // a 32 bit process with two linker namespaces, laid out as glibc does
+        let mut memory = Memory {
+            base: 0x1000,
+            data: vec![0; 0x1000],
+        };
+        // r_debug_extended for the default namespace, then for a dlmopen namespace
+        memory.put_words32(0x1000, &[2, 0x1100, 0, 0, 0xf7000000, 0x1040]);
+        memory.put_words32(0x1040, &[2, 0x1200, 0, 0, 0xf7000000, 0]);
+        // the executable, libc and a library in the second namespace
+        memory.put_words32(0x1100, &[0x56550000, 0x1300, 0x56553000, 0x1140, 0]);
+        memory.put_words32(0x1140, &[0xf7d00000, 0x1310, 0xf7f00000, 0, 0x1100]);
+        memory.put_words32(0x1200, &[0xf7a00000, 0x1330, 0xf7a10000, 0, 0]);
+        memory.put(0x1310, b"/lib/i386-linux-gnu/libc.so.6\0");
+        memory.put(0x1330, b"/tmp/libplugin.so\0");
+
+        let entries = read_link_map(&memory, 0x1000, Class::Bits32, Endianness::Little).unwrap();
+        let summary: Vec<(&str, u64, usize)> = entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.load_bias, entry.namespace))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("", 0x56550000, 0),
+                ("/lib/i386-linux-gnu/libc.so.6", 0xf7d00000, 0),
+                ("/tmp/libplugin.so", 0xf7a00000, 1),
+            ]
+        );
+        assert_eq!(entries[1].dynamic, 0xf7f00000);
+        assert_eq!(entries[2].address, 0x1200);
+
+        // r_debug from before glibc 2.35 doesn't have the namespace list
+        memory.put_words32(0x1000, &[1]);
+        let entries = read_link_map(&memory, 0x1000, Class::Bits32, Endianness::Little).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert!(read_link_map(&memory, 0x3000, Class::Bits32, Endianness::Little).is_err());
+    }
+}
@@ -1,3 +1,10 @@
+/// For finding the container and namespaces that a process runs in
+#[cfg(target_os = "linux")]
+pub mod container;
+/// For reading the modules loaded by the dynamic linker from its `link_map` list
+pub mod link_map;
+/// For grouping the memory maps of a process into the files they map
+pub mod module_map;
 /// Holds information about the process: memory map layout, parsed info
 /// for the binary and/or library, etc.
 pub mod process_info;
@@ -7,7 +14,14 @@ pub mod process_type;
 pub mod python_process_type;
 /// A trait implementation for Ruby processes
 pub mod ruby_process_type;
+/// For locating thread local variables in the static TLS blocks of a thread
+pub mod tls;
 
+#[cfg(target_os = "linux")]
+pub use container::{ContainerInfo, ContainerRuntime};
+pub use link_map::LinkMapEntry;
+pub use module_map::{MemoryMap, Module, ModuleMap};
+pub use process_info::{FoundSymbol, MissingSymbolsError, SymbolReport, SymbolSource};
 pub use process_type::ProcessType;
 pub use python_process_type::PythonProcessType;
 pub use ruby_process_type::RubyProcessType;
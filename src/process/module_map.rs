@@ -0,0 +1,521 @@
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{format_err, Error};
+use log::*;
+use memmap2::Mmap;
+use proc_maps::MapRange;
+
+use crate::binary_parser::target::strip_deleted;
+use crate::binary_parser::{elf_load_bias, elf_tls, parse_binary, BinaryInfo, Target, TlsInfo};
+
+/// A memory map of a process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    /// The start address of the map
+    pub start: u64,
+    /// The end address of the map (exclusive)
+    pub end: u64,
+    /// The offset in the mapped file that the map starts at, where the platform reports it
+    pub file_offset: Option<u64>,
+    /// The path of the mapped file, or the name of special maps like `[heap]`. The
+    /// ` (deleted)` suffix of deleted files is removed, see `deleted`.
+    pub path: Option<PathBuf>,
+    /// Whether the mapped file was deleted after it was mapped (or never had a path, like
+    /// `memfd:` files)
+    pub deleted: bool,
+    /// The major and minor numbers of the device that holds the mapped file, where the
+    /// platform reports it
+    pub device: Option<(u32, u32)>,
+    /// The inode of the mapped file, where the platform reports it
+    pub inode: Option<u64>,
+    /// Whether the map is readable
+    pub readable: bool,
+    /// Whether the map is writable
+    pub writable: bool,
+    /// Whether the map is executable
+    pub executable: bool,
+}
+
+impl MemoryMap {
+    /// Returns `true` if `addr` is inside of the map
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end
+    }
+
+    /// Returns the size of the map in bytes
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+impl From<&MapRange> for MemoryMap {
+    fn from(map: &MapRange) -> MemoryMap {
+        #[cfg(target_os = "linux")]
+        let (file_offset, device, inode) = (
+            Some(map.offset as u64),
+            crate::procfs::parse_device(&map.dev),
+            Some(map.inode as u64).filter(|&inode| inode != 0),
+        );
+        #[cfg(not(target_os = "linux"))]
+        let (file_offset, device, inode) = (None, None, None);
+
+        let path = map.filename().map(strip_deleted);
+        MemoryMap {
+            start: map.start() as u64,
+            end: (map.start() + map.size()) as u64,
+            file_offset,
+            path: path.map(|(path, _)| path.to_owned()),
+            deleted: path.is_some_and(|(_, deleted)| deleted),
+            device,
+            inode,
+            readable: map.is_read(),
+            writable: map.is_write(),
+            executable: map.is_exec(),
+        }
+    }
+}
+
+/// A file that is mapped into a process, like the executable or a shared library
+pub struct Module {
+    /// The path of the file, as seen by the process
+    pub path: PathBuf,
+    /// The memory maps of the file, sorted by address. This includes the anonymous map that
+    /// usually follows the writable data of a binary, which holds the rest of its BSS.
+    pub maps: Vec<MemoryMap>,
+    /// Whether this is the main executable of the process
+    pub is_bin: bool,
+    target: Target,
+    binary: OnceLock<Result<BinaryInfo, String>>,
+    dynamic_info: OnceLock<DynamicInfo>,
+}
+
+// the soname of a module and the modules it depends on, which place it in the search order,
+// and its TLS segment, which places it in the static TLS layout
+#[derive(Debug, Default)]
+struct DynamicInfo {
+    soname: Option<String>,
+    needed: Vec<String>,
+    tls: Option<TlsInfo>,
+}
+
+impl DynamicInfo {
+    fn from_binary(binary: &BinaryInfo) -> DynamicInfo {
+        DynamicInfo {
+            soname: binary.soname.clone(),
+            needed: binary.needed.clone(),
+            tls: binary.tls.clone(),
+        }
+    }
+}
+
+impl Module {
+    /// Returns the file name of the module, like `libc.so.6`
+    pub fn name(&self) -> Option<&str> {
+        self.path.file_name().and_then(OsStr::to_str)
+    }
+
+    /// Returns the lowest address the module is mapped at
+    pub fn start(&self) -> u64 {
+        self.maps.first().map_or(0, |map| map.start)
+    }
+
+    /// Returns the end of the highest map of the module
+    pub fn end(&self) -> u64 {
+        self.maps.last().map_or(0, |map| map.end)
+    }
+
+    /// Returns `true` if `addr` is inside of one of the maps of the module
+    pub fn contains(&self, addr: u64) -> bool {
+        self.maps.iter().any(|map| map.contains(addr))
+    }
+
+    /// Returns the parsed binary of the module. The binary is parsed the first time this is
+    /// called, and the result (or error) is kept for later calls.
+    pub fn binary(&self) -> Result<&BinaryInfo, Error> {
+        self.binary
+            .get_or_init(|| {
+                // the load bias is computed from the executable map where there is one
+                let map = self
+                    .maps
+                    .iter()
+                    .find(|map| map.executable)
+                    .or_else(|| self.maps.first())
+                    .ok_or_else(|| format!("{} isn't mapped", self.path.display()))?;
+                parse_binary(
+                    &self.target,
+                    &self.path,
+                    map.start,
+                    map.size(),
+                    map.file_offset,
+                    self.is_bin,
+                )
+                .map_err(|e| {
+                    debug!("Failed to parse {}: {}", self.path.display(), e);
+                    format!("Failed to parse {}: {}", self.path.display(), e)
+                })
+            })
+            .as_ref()
+            .map_err(|e| format_err!("{}", e))
+    }
+
+    /// Returns the load bias of the module, parsing it if it hasn't been already
+    pub fn load_bias(&self) -> Option<u64> {
+        self.binary().ok().map(|binary| binary.load_bias)
+    }
+
+    /// Returns the thread local storage segment (`PT_TLS`) of the module, if it has one.
+    ///
+    /// ELF modules are only read for their program headers here, like for
+    /// [`ModuleMap::search_order`], so the module id of the segment isn't set unless the module
+    /// was parsed as the executable.
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        self.dynamic_info().tls.as_ref()
+    }
+
+    // ELF files are only read for their dynamic section and program headers here, since
+    // parse_binary can go looking for debug files and checksum or decompress them. Other
+    // formats don't have that cost, and are parsed.
+    fn dynamic_info(&self) -> &DynamicInfo {
+        self.dynamic_info.get_or_init(|| {
+            if let Some(Ok(binary)) = self.binary.get() {
+                return DynamicInfo::from_binary(binary);
+            }
+            match self.read_elf_dynamic_info() {
+                Ok(Some(info)) => info,
+                Ok(None) => match self.binary() {
+                    Ok(binary) => DynamicInfo::from_binary(binary),
+                    Err(_) => DynamicInfo::default(),
+                },
+                Err(e) => {
+                    debug!(
+                        "Failed to read dynamic section of {}: {}",
+                        self.path.display(),
+                        e
+                    );
+                    DynamicInfo::default()
+                }
+            }
+        })
+    }
+
+    // returns None for files that aren't ELF
+    fn read_elf_dynamic_info(&self) -> Result<Option<DynamicInfo>, Error> {
+        let map = self
+            .maps
+            .first()
+            .ok_or_else(|| format_err!("{} isn't mapped", self.path.display()))?;
+        let (path, _) = self
+            .target
+            .mapped_file(&self.path, self.is_bin, map.start, map.size());
+        let file = File::open(&path)?;
+        let buffer = unsafe { Mmap::map(&file)? };
+        if !buffer.starts_with(goblin::elf::header::ELFMAG) {
+            return Ok(None);
+        }
+        let elf = goblin::elf::Elf::parse(&buffer)?;
+        // the load bias is computed from the executable map, like parse_binary does
+        let map = self.maps.iter().find(|map| map.executable).unwrap_or(map);
+        let tls = elf_load_bias(&elf, map.start, map.file_offset)
+            .and_then(|load_bias| elf_tls(&elf, load_bias));
+        Ok(Some(DynamicInfo {
+            soname: elf.soname.map(|name| name.to_owned()),
+            needed: elf.libraries.iter().map(|lib| lib.to_string()).collect(),
+            tls,
+        }))
+    }
+}
+
+impl std::fmt::Debug for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Module")
+            .field("path", &self.path)
+            .field("maps", &self.maps)
+            .field("is_bin", &self.is_bin)
+            .field("parsed", &self.binary.get().is_some())
+            .finish()
+    }
+}
+
+/// The files mapped into a process, grouped from its memory maps
+#[derive(Debug)]
+pub struct ModuleMap {
+    // sorted by start address
+    modules: Vec<Module>,
+}
+
+impl ModuleMap {
+    /// Groups the memory maps of a process into modules. `exe` is the path of the process's
+    /// executable, and `target` describes how to read the files of the modules.
+    ///
+    /// Consecutive maps of the same file make up a module. Maps that aren't backed by a file
+    /// (like `[heap]` or `[stack]`) are skipped, apart from an anonymous writable map right
+    /// after a module's data, which is where the dynamic linker puts the rest of the BSS.
+    pub fn new<I>(target: &Target, exe: Option<&Path>, maps: I) -> ModuleMap
+    where
+        I: IntoIterator<Item = MemoryMap>,
+    {
+        let mut maps: Vec<MemoryMap> = maps.into_iter().collect();
+        maps.sort_by_key(|map| map.start);
+
+        let mut modules: Vec<Module> = Vec::new();
+        for map in maps {
+            let path = match &map.path {
+                Some(path) if is_file_path(path) => path.clone(),
+                Some(_) => continue,
+                None => {
+                    if let Some(last) = modules.last_mut() {
+                        let previous = last.maps.last().unwrap();
+                        if map.writable && previous.writable && previous.end == map.start {
+                            last.maps.push(map);
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(last) = modules.last_mut() {
+                if last.path == path && last.maps.iter().all(|m| m.path.is_some()) {
+                    last.maps.push(map);
+                    continue;
+                }
+            }
+            modules.push(Module {
+                is_bin: exe == Some(path.as_path()),
+                path,
+                maps: vec![map],
+                target: target.clone(),
+                binary: OnceLock::new(),
+                dynamic_info: OnceLock::new(),
+            });
+        }
+        ModuleMap { modules }
+    }
+
+    /// Returns the modules, sorted by address
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    /// Returns the main executable of the process
+    pub fn binary(&self) -> Option<&Module> {
+        self.modules.iter().find(|module| module.is_bin)
+    }
+
+    /// Returns the module that `addr` is mapped in, if any
+    pub fn module_at(&self, addr: u64) -> Option<&Module> {
+        self.index_at(addr).map(|index| &self.modules[index])
+    }
+
+    // like module_at, but returns the index of the module
+    pub(crate) fn index_at(&self, addr: u64) -> Option<usize> {
+        let end = self
+            .modules
+            .partition_point(|module| module.start() <= addr);
+        self.modules[..end]
+            .iter()
+            .rposition(|module| module.contains(addr))
+    }
+
+    /// Returns the first module whose file name (like `libc.so.6`) or full path is `name`
+    pub fn find(&self, name: &str) -> Option<&Module> {
+        self.modules
+            .iter()
+            .find(|module| module.name() == Some(name) || module.path == Path::new(name))
+    }
+
+    /// Iterates over the modules whose paths match `regex`
+    pub fn matching<'a>(&'a self, regex: &'a regex::Regex) -> impl Iterator<Item = &'a Module> {
+        self.modules
+            .iter()
+            .filter(move |module| regex.is_match(&module.path.to_string_lossy()))
+    }
+
+    /// Iterates over the modules in the order that the dynamic linker searches them for
+    /// symbols: the executable, then its dependencies breadth first in `DT_NEEDED` order, and
+    /// then the modules that nothing depends on (which were loaded with `dlopen`), by address.
+    ///
+    /// The dependencies of ELF modules are read from their dynamic sections as the iteration
+    /// reaches them, without parsing the modules. Modules in other formats are parsed.
+    pub fn search_order(&self) -> impl Iterator<Item = &Module> {
+        self.search_order_indices()
+            .map(move |index| &self.modules[index])
+    }
+
+    // like search_order, but yields the indices of the modules
+    pub(crate) fn search_order_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut visited = vec![false; self.modules.len()];
+        let mut queue: VecDeque<usize> = self
+            .modules
+            .iter()
+            .position(|module| module.is_bin)
+            .into_iter()
+            .collect();
+        let mut unneeded = 0;
+        std::iter::from_fn(move || {
+            let index = match queue.iter().position(|&index| !visited[index]) {
+                Some(position) => {
+                    queue.drain(..position);
+                    queue.pop_front()?
+                }
+                None => {
+                    queue.clear();
+                    unneeded += visited[unneeded..].iter().position(|&v| !v)?;
+                    unneeded
+                }
+            };
+            visited[index] = true;
+            queue.extend(
+                self.modules[index]
+                    .dynamic_info()
+                    .needed
+                    .iter()
+                    .filter_map(|name| self.find_needed(name)),
+            );
+            Some(index)
+        })
+    }
+
+    // Finds the module for a DT_NEEDED entry. These usually hold the soname of the library,
+    // which can differ from the name of the file it resolves to (like libstdc++.so.6 for
+    // libstdc++.so.6.0.30), so the sonames of files whose names start with it are checked too.
+    fn find_needed(&self, needed: &str) -> Option<usize> {
+        let needed = Path::new(needed)
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or(needed);
+        let same_name = |a: &str, b: &str| {
+            if cfg!(windows) {
+                a.eq_ignore_ascii_case(b)
+            } else {
+                a == b
+            }
+        };
+        let names = || {
+            self.modules
+                .iter()
+                .enumerate()
+                .filter_map(|(index, module)| Some((index, module.name()?)))
+        };
+        // files can also be mapped as data (like by a profiler reading them), which leaves
+        // them without an executable map, so the first loaded module with the name wins
+        let loaded = |index: usize| self.modules[index].maps.iter().any(|map| map.executable);
+        names()
+            .filter(|(_, name)| same_name(name, needed))
+            .min_by_key(|&(index, _)| !loaded(index))
+            .or_else(|| {
+                names()
+                    .filter(|&(index, name)| {
+                        name.starts_with(needed)
+                            && self.modules[index]
+                                .dynamic_info()
+                                .soname
+                                .as_deref()
+                                .is_some_and(|soname| same_name(soname, needed))
+                    })
+                    .min_by_key(|&(index, _)| !loaded(index))
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+// special maps like [heap], [vdso] or anon_inode:[perf_event] aren't files
+fn is_file_path(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    !path.is_empty() && !path.starts_with('[') && !path.starts_with("anon_inode:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_parser::tests::{fixture, BASE};
+
+    fn map(start: u64, end: u64, offset: u64, path: Option<PathBuf>, perms: &str) -> MemoryMap {
+        MemoryMap {
+            start,
+            end,
+            file_offset: Some(offset),
+            path,
+            deleted: false,
+            device: None,
+            inode: None,
+            readable: perms.contains('r'),
+            writable: perms.contains('w'),
+            executable: perms.contains('x'),
+        }
+    }
+
+    #[test]
+    fn test_module_map() {
+        let lib = Some(fixture("libfixture.stripped"));
+        let usdt = Some(fixture("libusdt.stripped"));
+        let maps = vec![
+            map(BASE + 0x3000, BASE + 0x4000, 0x2000, lib.clone(), "r--"),
+            map(BASE, BASE + 0x1000, 0, lib.clone(), "r--"),
+            map(BASE + 0x1000, BASE + 0x2000, 0x1000, lib.clone(), "r-x"),
+            map(BASE + 0x4000, BASE + 0x5000, 0x3000, lib.clone(), "rw-"),
+            // the rest of the BSS
+            map(BASE + 0x5000, BASE + 0x6000, 0, None, "rw-"),
+            map(BASE + 0x10000, BASE + 0x11000, 0, None, "rw-"),
+            map(BASE + 0x20000, BASE + 0x21000, 0, usdt.clone(), "r--"),
+            map(BASE + 0x21000, BASE + 0x22000, 0x1000, usdt.clone(), "r-x"),
+            map(
+                BASE + 0x30000,
+                BASE + 0x31000,
+                0,
+                Some("[heap]".into()),
+                "rw-",
+            ),
+        ];
+        let modules = ModuleMap::new(&Target::host(), None, maps);
+
+        assert_eq!(modules.modules().len(), 2);
+        let fixture_module = &modules.modules()[0];
+        assert_eq!(fixture_module.name(), Some("libfixture.stripped"));
+        assert_eq!(fixture_module.maps.len(), 5);
+        assert_eq!(
+            (fixture_module.start(), fixture_module.end()),
+            (BASE, BASE + 0x6000)
+        );
+        assert!(modules.binary().is_none());
+
+        let at = |addr| modules.module_at(addr).and_then(Module::name);
+        assert_eq!(at(BASE + 0x1234), Some("libfixture.stripped"));
+        assert_eq!(at(BASE + 0x5800), Some("libfixture.stripped"));
+        assert_eq!(at(BASE + 0x2000), None);
+        assert_eq!(at(BASE + 0x10000), None);
+        assert_eq!(at(BASE + 0x21000), Some("libusdt.stripped"));
+        assert_eq!(at(BASE + 0x30000), None);
+
+        let module = modules.find("libusdt.stripped").unwrap();
+        assert_eq!(module.path, fixture("libusdt.stripped"));
+        assert!(modules
+            .find(usdt.as_ref().unwrap().to_str().unwrap())
+            .is_some());
+        assert!(modules.find("libc.so.6").is_none());
+        let regex = regex::Regex::new(r"/lib\w+\.stripped$").unwrap();
+        assert_eq!(modules.matching(&regex).count(), 2);
+
+        // placing modules in the search order or the TLS layout only reads their headers
+        assert_eq!(modules.search_order().count(), 2);
+        let tls = fixture_module.tls().unwrap().clone();
+        assert_eq!((tls.image_addr, tls.size), (BASE + 0x3dc4, 8));
+        assert!(module.tls().is_none());
+        assert!(modules
+            .modules()
+            .iter()
+            .all(|module| module.binary.get().is_none()));
+
+        // modules are parsed on demand, using the executable map for the load bias
+        assert_eq!(module.load_bias(), Some(BASE + 0x20000));
+        let binary = fixture_module.binary().unwrap();
+        assert_eq!(
+            binary.symbols.address("fixture_global_data"),
+            Some(BASE + 0x4014)
+        );
+        assert_eq!(binary.tls.as_ref(), Some(&tls));
+    }
+}
@@ -1,12 +1,25 @@
 use anyhow::{format_err, Context, Error};
 use log::*;
-use proc_maps::{get_process_maps, MapRange};
+#[cfg(not(target_os = "linux"))]
+use proc_maps::get_process_maps;
 #[cfg(target_os = "windows")]
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-use crate::binary_parser::{parse_binary, BinaryInfo};
+#[cfg(target_os = "macos")]
+use crate::binary_parser::parse_mach_segment;
+use crate::binary_parser::target::strip_deleted;
+use crate::binary_parser::{
+    parse_binary, Architecture, BinaryInfo, Class, Endianness, FileSource, Symbol, SymbolKind,
+    Target, TlsInfo, UsdtProbe,
+};
+#[cfg(target_os = "linux")]
+use crate::process::container::ContainerInfo;
+use crate::process::link_map::{find_r_debug, read_link_map, LinkMapEntry};
+use crate::process::module_map::{MemoryMap, ModuleMap};
+use crate::process::tls::{static_tls_offsets, tls_address, TlsVariant};
 use crate::process::ProcessType;
+use crate::procfs::Procfs;
 
 /// Holds information about the process: memory map layout, parsed info
 /// for the binary and/or library, etc.
@@ -15,24 +28,76 @@ pub struct ProcessInfo {
     pub binary: Option<BinaryInfo>,
     /// Metadata about the library, if any
     pub library: Option<BinaryInfo>,
-    /// The binary or library's mapped memory ranges
-    pub maps: Vec<MapRange>,
+    /// The memory maps of the process, including the device and inode of mapped files on
+    /// Linux, which are read from the proc filesystem the process was read through. See
+    /// [`maps_contain_addr`](ProcessInfo::maps_contain_addr) for checking addresses against
+    /// them.
+    pub memory_maps: Vec<MemoryMap>,
+    /// Every file mapped into the process, which are parsed on demand
+    pub modules: ModuleMap,
+    /// The modules loaded by the dynamic linker, in load order, if its `link_map` list could
+    /// be read. This is only available for ELF binaries.
+    pub link_map: Option<Vec<LinkMapEntry>>,
     /// The file path to the binary or library
     pub path: PathBuf,
-    /// Whether the process is running in a Docker container
+    /// The container and namespaces the process runs in
     #[cfg(target_os = "linux")]
-    pub dockerized: bool,
+    pub container: ContainerInfo,
 }
 
 impl ProcessInfo {
     /// Constructs a new `ProcessInfo` that can be used to get symbol information.
+    ///
+    /// Fails with a [`MissingSymbolsError`] if any of the symbols in
+    /// [`ProcessType::required_symbols`] can't be found in the binary or the library.
     pub fn new<T>(process: &remoteprocess::Process) -> Result<Self, Error>
     where
         T: crate::process::ProcessType,
     {
-        let filename = process
-            .exe()
+        Self::with_procfs::<T>(process, &Procfs::default())
+    }
+
+    /// Like [`new`](ProcessInfo::new), but reads the memory maps, executable and namespaces of
+    /// Linux processes from the proc filesystem at `procfs` rather than `/proc`
+    pub fn with_procfs<T>(process: &remoteprocess::Process, procfs: &Procfs) -> Result<Self, Error>
+    where
+        T: crate::process::ProcessType,
+    {
+        let target = if cfg!(target_os = "linux") {
+            Target::process_in(procfs, process.pid)
+        } else {
+            Target::process(process.pid)
+        };
+        Self::with_target::<T>(process, &target)
+    }
+
+    /// Like [`new`](ProcessInfo::new), but reads the binaries of the process as `target`
+    /// describes, like with extra directories to search for debug files. The memory maps and
+    /// executable of Linux processes are read from the proc filesystem of the target if it
+    /// has one, and from `/proc` otherwise. When the target's architecture isn't set, it's
+    /// read from the process.
+    pub fn with_target<T>(process: &remoteprocess::Process, target: &Target) -> Result<Self, Error>
+    where
+        T: crate::process::ProcessType,
+    {
+        let procfs = match &target.files {
+            FileSource::Procfs(procfs, _) => procfs.clone(),
+            _ => Procfs::default(),
+        };
+        let procfs = &procfs;
+        #[cfg(target_os = "linux")]
+        let filename = procfs
+            .exe(process.pid)
+            .map(|path| path.to_string_lossy().into_owned());
+        #[cfg(not(target_os = "linux"))]
+        let filename = process.exe();
+        let filename = filename
             .context("Failed to get process executable name. Check that the process is running.")?;
+        // the executable of the process was deleted or replaced since it started
+        let filename = match strip_deleted(std::path::Path::new(&filename)) {
+            (path, true) => path.to_string_lossy().into_owned(),
+            (_, false) => filename,
+        };
 
         #[cfg(windows)]
         let filename = filename.to_lowercase();
@@ -43,37 +108,43 @@ impl ProcessInfo {
         #[cfg(not(windows))]
         let is_bin = |pathname: &str| pathname == filename;
 
-        let maps = get_process_maps(process.pid)?;
+        #[cfg(target_os = "linux")]
+        let maps = procfs.maps(process.pid)?;
+        #[cfg(not(target_os = "linux"))]
+        let maps: Vec<MemoryMap> = get_process_maps(process.pid)?
+            .iter()
+            .map(MemoryMap::from)
+            .collect();
+        let target = target
+            .clone()
+            .with_arch(target.arch.or_else(|| process_arch(procfs, process.pid)));
         info!("Got virtual memory maps from pid {}:", process.pid);
         for map in &maps {
             debug!(
-                "map: {:016x}-{:016x} {}{}{} {}",
-                map.start(),
-                map.start() + map.size(),
-                if map.is_read() { 'r' } else { '-' },
-                if map.is_write() { 'w' } else { '-' },
-                if map.is_exec() { 'x' } else { '-' },
-                map.filename()
-                    .unwrap_or(&std::path::PathBuf::from(""))
-                    .display()
+                "map: {:016x}-{:016x} {}{}{} {}{}",
+                map.start,
+                map.end,
+                if map.readable { 'r' } else { '-' },
+                if map.writable { 'w' } else { '-' },
+                if map.executable { 'x' } else { '-' },
+                map.path
+                    .as_deref()
+                    .unwrap_or_else(|| std::path::Path::new(""))
+                    .display(),
+                if map.deleted { " (deleted)" } else { "" }
             );
         }
 
+        let binary_map = find_binary_map(procfs, process.pid, &filename, &maps, is_bin);
         let (binary, filename) = {
-            let map = maps.iter().find(|m| {
-                if let Some(pathname) = m.filename() {
-                    if let Some(pathname) = pathname.to_str() {
-                        return is_bin(pathname) && m.is_exec();
-                    }
-                }
-                false
-            });
-
-            let map = match map {
+            let map = match binary_map {
                 Some(map) => map,
                 None => {
                     // https://github.com/benfred/py-spy/issues/40
-                    warn!("Failed to find '{}' in virtual memory maps, falling back to first map region", filename);
+                    warn!(
+                        "Falling back to the first memory map region for '{}'",
+                        filename
+                    );
                     if maps.is_empty() {
                         return Err(format_err!("No memory map regions found for process"));
                     }
@@ -84,51 +155,27 @@ impl ProcessInfo {
             let filename = PathBuf::from(filename);
 
             // TODO: consistent types? u64 -> usize? for map.start etc
-            #[allow(unused_mut)]
+            #[allow(unused_mut, clippy::bind_instead_of_map)]
             let binary = parse_binary(
-                process.pid,
+                &target,
                 &filename,
-                map.start() as u64,
-                map.size() as u64,
+                map.start,
+                map.size(),
+                map.file_offset,
                 true,
             )
             .and_then(|mut pb| {
                 // windows symbols are stored in separate files (.pdb), load
                 #[cfg(windows)]
                 {
-                    get_windows_symbols::<T>(process.pid, &filename, map.start() as u64)
+                    get_windows_symbols::<T>(process.pid, &filename, map.start)
                         .map(|symbols| {
-                            pb.symbols.extend(symbols);
+                            pb.symbols.extend(windows_symbols(symbols));
                             pb
                         })
                         .map_err(|err| err.into())
                 }
 
-                // For macOS, need to adjust main binary symbols by subtracting _mh_execute_header
-                // (which was added to map.start already, so undo that here)
-                #[cfg(target_os = "macos")]
-                {
-                    if !pb.symbols.contains_key("_mh_execute_header") {
-                        return Err(format_err!(
-                            "Failed to find _mh_execute_header symbol in binary {}",
-                            filename.display()
-                        ));
-                    }
-
-                    let mhe_header_sym_address = pb
-                        .symbols
-                        .get("_mh_execute_header")
-                        .ok_or_else(|| format_err!("_mh_execute_header not found in symbols"))?;
-                    let offset = mhe_header_sym_address - map.start() as u64;
-                    for address in pb.symbols.values_mut() {
-                        *address -= offset;
-                    }
-
-                    if pb.bss_addr != 0 {
-                        pb.bss_addr -= offset;
-                    }
-                }
-
                 #[cfg(not(windows))]
                 Ok(pb)
             });
@@ -139,33 +186,36 @@ impl ProcessInfo {
         // likewise handle library for versions compiled with --enabled-shared
         let library = {
             let libmap = maps.iter().find(|m| {
-                if let Some(path) = m.filename() {
+                if let Some(path) = &m.path {
                     #[cfg(target_os = "windows")]
-                    return is_lib::<T>(path) && m.is_read();
+                    return is_lib::<T>(path) && m.readable;
                     #[cfg(not(target_os = "windows"))]
-                    return is_lib::<T>(path) && m.is_exec();
+                    return is_lib::<T>(path) && m.executable;
                 }
                 false
             });
 
             let mut library: Option<BinaryInfo> = None;
             if let Some(libmap) = libmap {
-                if let Some(filename) = &libmap.filename() {
+                if let Some(filename) = &libmap.path {
                     info!("Found library @ {}", filename.display());
                     #[allow(unused_mut)]
                     let mut parsed = parse_binary(
-                        process.pid,
+                        &target,
                         filename,
-                        libmap.start() as u64,
-                        libmap.size() as u64,
+                        libmap.start,
+                        libmap.size(),
+                        libmap.file_offset,
                         false,
                     )?;
                     #[cfg(windows)]
-                    parsed.symbols.extend(get_windows_symbols::<T>(
-                        process.pid,
-                        filename,
-                        libmap.start() as u64,
-                    )?);
+                    parsed
+                        .symbols
+                        .extend(windows_symbols(get_windows_symbols::<T>(
+                            process.pid,
+                            filename,
+                            libmap.start,
+                        )?));
                     library = Some(parsed);
                 }
             }
@@ -199,21 +249,15 @@ impl ProcessInfo {
                     if let Some(dyld_data) = dyld_data {
                         info!("Found library from dyld @ {}", dyld_data.filename.display());
 
-                        let mut binary = parse_binary(
-                            process.pid,
+                        // the segments of libraries in the dyld shared cache aren't loaded
+                        // next to each other, so the library is placed by its data segment
+                        library = Some(parse_mach_segment(
+                            &target,
                             &dyld_data.filename,
+                            "__DATA",
                             dyld_data.segment.vmaddr,
                             dyld_data.segment.vmsize,
-                            false,
-                        )?;
-
-                        // TODO: bss addr offsets returned from parsing binary are wrong
-                        // (assumes data section isn't split from text section like done here).
-                        // BSS occurs somewhere in the data section, just scan that
-                        // (could later tighten this up to look at segment sections too)
-                        binary.bss_addr = dyld_data.segment.vmaddr;
-                        binary.bss_size = dyld_data.segment.vmsize;
-                        library = Some(binary);
+                        )?);
                     }
                 }
             }
@@ -228,42 +272,762 @@ impl ProcessInfo {
         };
 
         #[cfg(target_os = "linux")]
-        let dockerized = is_dockerized(process.pid).unwrap_or(false);
+        let container = ContainerInfo::for_process(procfs, process.pid);
 
-        Ok(Self {
+        let binary_path = binary_map.and_then(|map| map.path.clone());
+        let modules = ModuleMap::new(&target, binary_path.as_deref(), maps.iter().cloned());
+        let mut process_info = Self {
             binary,
             library,
-            maps,
+            memory_maps: maps,
+            modules,
+            link_map: None,
             path: filename,
             #[cfg(target_os = "linux")]
-            dockerized,
-        })
+            container,
+        };
+        process_info.link_map = process_info.read_link_map(process);
+        if let Some(link_map) = &process_info.link_map {
+            process_info.check_link_map(link_map);
+        }
+        // TLS module ids are handed out in load order, which is only known from the link_map
+        if process_info
+            .library
+            .as_ref()
+            .is_some_and(|l| l.tls.is_some())
+        {
+            let module_id = match process_info.static_tls_modules(SymbolSource::Library) {
+                Ok(modules) => modules.last().and_then(|module| module.module_id),
+                Err(e) => {
+                    debug!("Failed to find the TLS module id of the library: {}", e);
+                    None
+                }
+            };
+            if let Some(tls) = process_info.library.as_mut().and_then(|l| l.tls.as_mut()) {
+                tls.module_id = module_id;
+            }
+        }
+        let report = process_info.report_symbols(T::required_symbols(), Vec::new());
+        info!("{}", report);
+        if !report.missing_required.is_empty() {
+            return Err(MissingSymbolsError {
+                path: process_info.path,
+                report,
+            }
+            .into());
+        }
+        Ok(process_info)
+    }
+
+    /// Looks up the required and optional symbols of the `ProcessType` in the binary and the
+    /// library, reporting where each one was found. Only the required symbols are checked when
+    /// the `ProcessInfo` is constructed, so this is how to find out which optional ones are
+    /// missing.
+    pub fn check_symbols<T>(&self) -> SymbolReport
+    where
+        T: ProcessType,
+    {
+        self.report_symbols(T::required_symbols(), T::optional_symbols())
+    }
+
+    fn report_symbols(&self, required: Vec<String>, optional: Vec<String>) -> SymbolReport {
+        let mut report = SymbolReport::default();
+        let required = required.into_iter().map(|name| (name, true));
+        let optional = optional.into_iter().map(|name| (name, false));
+        for (name, is_required) in required.chain(optional) {
+            match self.lookup_symbol(&name) {
+                Some((source, symbol)) => report.found.push(FoundSymbol {
+                    address: symbol.address,
+                    name,
+                    source,
+                }),
+                None if is_required => report.missing_required.push(name),
+                None => report.missing_optional.push(name),
+            }
+        }
+        report
+    }
+
+    /// Returns `true` if `addr` is inside of one of the memory maps of the process, like
+    /// `proc_maps::maps_contain_addr`
+    pub fn maps_contain_addr(&self, addr: u64) -> bool {
+        self.memory_maps.iter().any(|map| map.contains(addr))
+    }
+
+    /// Returns `true` if all `size` bytes starting at `addr` are mapped, possibly by several
+    /// adjacent maps, like `proc_maps::maps_contain_addr_range`
+    pub fn maps_contain_addr_range(&self, addr: u64, size: u64) -> bool {
+        let end = match addr.checked_add(size) {
+            Some(end) if size > 0 => end,
+            _ => return false,
+        };
+        let mut addr = addr;
+        while addr < end {
+            match self.memory_maps.iter().find(|map| map.contains(addr)) {
+                Some(map) => addr = map.end,
+                None => return false,
+            }
+        }
+        true
     }
 
     /// Gets the memory address of the named symbol, if it exists.
     pub fn get_symbol(&self, symbol: &str) -> Option<&u64> {
-        if let Some(ref pb) = self.binary {
-            if let Some(addr) = pb.symbols.get(symbol) {
-                info!("got symbol {} (0x{:016x}) from binary", symbol, addr);
-                return Some(addr);
+        self.lookup_symbol(symbol).map(|(_, sym)| &sym.address)
+    }
+
+    /// Gets the full record of the named symbol, along with whether it was found in the
+    /// binary or the library. The binary is searched first.
+    ///
+    /// Other modules aren't searched, see [`resolve_symbol`](ProcessInfo::resolve_symbol).
+    pub fn lookup_symbol(&self, symbol: &str) -> Option<(SymbolSource, &Symbol)> {
+        let found = self
+            .modules()
+            .find_map(|(source, module)| Some((source, module.symbols.get(symbol)?)));
+        if let Some((source, sym)) = found {
+            info!(
+                "got symbol {} (0x{:016x}) from {:?}",
+                symbol, sym.address, source
+            );
+        }
+        found
+    }
+
+    /// Looks up an exported symbol in every module of the process, like the dynamic linker
+    /// binds references to it. Modules are searched in the dynamic linker's order (see
+    /// [`ModuleMap::search_order`]): the binary, then its dependencies in load order and then
+    /// libraries loaded with `dlopen`. The first module that exports the symbol wins.
+    ///
+    /// Modules other than the binary and the library are parsed as the search reaches them,
+    /// so this can be much slower than [`lookup_symbol`](ProcessInfo::lookup_symbol) for
+    /// symbols that are defined late in the order or not at all.
+    pub fn resolve_symbol(&self, symbol: &str) -> Option<(SymbolSource, &Symbol)> {
+        let found = self.search_order().find_map(|(source, module)| {
+            let sym = module.symbols.get(symbol)?;
+            if sym.is_exported() {
+                Some((source, sym))
+            } else {
+                None
+            }
+        });
+        if let Some((source, sym)) = found {
+            info!(
+                "resolved symbol {} (0x{:016x}) to {:?}",
+                symbol, sym.address, source
+            );
+        }
+        found
+    }
+
+    /// Gets the first of `names` that exists in the process, for symbols that were renamed
+    /// between runtime versions. Each name is looked up in the binary and the library (like
+    /// [`lookup_symbol`](ProcessInfo::lookup_symbol)) before moving on to the next one.
+    pub fn lookup_first<'a>(&self, names: &[&'a str]) -> Option<(&'a str, SymbolSource, &Symbol)> {
+        names.iter().find_map(|&name| {
+            self.lookup_symbol(name)
+                .map(|(source, symbol)| (name, source, symbol))
+        })
+    }
+
+    /// Gets a symbol by its demangled C++ or Rust name (see
+    /// [`demangle`](crate::binary_parser::demangle)), returning its mangled name along with
+    /// where it was found. The binary is searched first.
+    pub fn lookup_demangled(&self, name: &str) -> Option<(&str, SymbolSource, &Symbol)> {
+        self.modules().find_map(|(source, module)| {
+            module
+                .symbols
+                .get_demangled(name)
+                .map(|(mangled, symbol)| (mangled, source, symbol))
+        })
+    }
+
+    /// Returns every symbol in the binary and the library whose name matches `regex`
+    pub fn find_symbols(&self, regex: &regex::Regex) -> Vec<(&str, SymbolSource, &Symbol)> {
+        self.modules()
+            .flat_map(|(source, module)| {
+                module
+                    .symbols
+                    .iter()
+                    .filter(|(name, _)| regex.is_match(name))
+                    .map(move |(name, symbol)| (name, source, symbol))
+            })
+            .collect()
+    }
+
+    /// Returns the USDT probes of the binary and the library, along with which of them each
+    /// probe is in. Probe and semaphore addresses are in the process's memory.
+    pub fn usdt_probes(&self) -> Vec<(SymbolSource, &UsdtProbe)> {
+        self.modules()
+            .flat_map(|(source, module)| {
+                module.usdt_probes.iter().map(move |probe| (source, probe))
+            })
+            .collect()
+    }
+
+    /// Returns the CPU architecture of the process, from its binary (or the library, if the
+    /// binary couldn't be parsed)
+    pub fn arch(&self) -> Option<Architecture> {
+        self.main_module().map(|module| module.arch)
+    }
+
+    /// Returns whether the process uses 32 or 64 bit pointers
+    pub fn class(&self) -> Option<Class> {
+        self.main_module().map(|module| module.class)
+    }
+
+    /// Returns the byte order of the process
+    pub fn endianness(&self) -> Option<Endianness> {
+        self.main_module().map(|module| module.endianness)
+    }
+
+    /// Returns the size in bytes of pointers in the process
+    pub fn pointer_size(&self) -> Option<usize> {
+        self.class().map(Class::pointer_size)
+    }
+
+    fn read_link_map(&self, process: &remoteprocess::Process) -> Option<Vec<LinkMapEntry>> {
+        let binary = self.main_module().filter(|binary| binary.is_elf())?;
+        let r_debug = find_r_debug(process, binary).or_else(|| {
+            // programs started by running ld.so directly don't have DT_DEBUG set
+            self.get_symbol("_r_debug").copied()
+        })?;
+        match read_link_map(process, r_debug, binary.class, binary.endianness) {
+            Ok(link_map) => {
+                for entry in &link_map {
+                    debug!(
+                        "link_map: {:016x} {} (namespace {})",
+                        entry.load_bias, entry.name, entry.namespace
+                    );
+                }
+                Some(link_map)
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to read the link_map list of the dynamic linker: {}",
+                    e
+                );
+                None
             }
         }
+    }
 
-        if let Some(ref binary) = self.library {
-            if let Some(addr) = binary.symbols.get(symbol) {
-                info!("got symbol {} (0x{:016x}) from library", symbol, addr);
-                return Some(addr);
+    // compares what the dynamic linker loaded with what was found from the memory maps
+    fn check_link_map(&self, link_map: &[LinkMapEntry]) {
+        let same_file = |entry: &LinkMapEntry, path: &std::path::Path| {
+            let name = std::path::Path::new(&entry.name);
+            // l_name isn't canonicalized, so /lib/libc.so.6 can be mapped as /usr/lib/libc.so.6
+            name == path || (name.is_absolute() && name.file_name() == path.file_name())
+        };
+
+        // the executable is always the first module
+        if let (Some(binary), Some(entry)) = (&self.binary, link_map.first()) {
+            if entry.load_bias != binary.load_bias {
+                warn!(
+                    "Load bias of {} from the memory maps ({:#x}) differs from the link_map ({:#x})",
+                    binary.filename.display(),
+                    binary.load_bias,
+                    entry.load_bias
+                );
+            }
+        }
+        if let Some(library) = &self.library {
+            // the library can be read from another path than the one it was mapped from
+            let path = self
+                .modules
+                .module_at(library.addr)
+                .map_or(library.filename.as_path(), |module| module.path.as_path());
+            match link_map.iter().find(|entry| same_file(entry, path)) {
+                Some(entry) if entry.load_bias != library.load_bias => warn!(
+                    "Load bias of {} from the memory maps ({:#x}) differs from the link_map ({:#x})",
+                    library.filename.display(),
+                    library.load_bias,
+                    entry.load_bias
+                ),
+                Some(_) => {}
+                None => warn!(
+                    "{} isn't in the link_map of the dynamic linker",
+                    library.filename.display()
+                ),
             }
         }
-        None
+        for entry in link_map.iter().skip(1) {
+            let mapped = self
+                .modules
+                .modules()
+                .iter()
+                .any(|module| same_file(entry, &module.path));
+            // the vdso has a name like linux-vdso.so.1, which isn't a file
+            if !mapped && std::path::Path::new(&entry.name).is_absolute() {
+                info!(
+                    "{} was loaded by the dynamic linker, but isn't in the memory maps",
+                    entry.name
+                );
+            }
+        }
+    }
+
+    fn main_module(&self) -> Option<&BinaryInfo> {
+        self.binary.as_ref().or(self.library.as_ref())
+    }
+
+    // the modules in the dynamic linker's search order, using the already parsed binary and
+    // library for their modules. The library is searched after the binary if it isn't mapped.
+    fn search_order(&self) -> impl Iterator<Item = (SymbolSource, &BinaryInfo)> {
+        let library_module = self.library_module();
+        let binary = self.binary.as_ref().map(|b| (SymbolSource::Binary, b));
+        let unmapped_library = match library_module {
+            Some(_) => None,
+            None => self.library.as_ref().map(|l| (SymbolSource::Library, l)),
+        };
+
+        let modules = self
+            .modules
+            .search_order_indices()
+            .filter_map(move |index| {
+                let module = &self.modules.modules()[index];
+                if module.is_bin {
+                    None
+                } else if Some(index) == library_module {
+                    self.library.as_ref().map(|l| (SymbolSource::Library, l))
+                } else {
+                    module
+                        .binary()
+                        .ok()
+                        .map(|b| (SymbolSource::Module(index), b))
+                }
+            });
+        binary.into_iter().chain(unmapped_library).chain(modules)
+    }
+
+    // the index of the library in the modules of the process, if it's mapped as one
+    fn library_module(&self) -> Option<usize> {
+        self.library
+            .as_ref()
+            .and_then(|library| self.modules.index_at(library.addr))
+    }
+
+    fn modules(&self) -> impl Iterator<Item = (SymbolSource, &BinaryInfo)> {
+        let binary = self.binary.as_ref().map(|b| (SymbolSource::Binary, b));
+        let library = self.library.as_ref().map(|l| (SymbolSource::Library, l));
+        binary.into_iter().chain(library)
+    }
+
+    /// Gets the address of a thread local variable (like `_Py_tss_tstate` or `ruby_current_ec`)
+    /// for the thread whose thread pointer is `thread_pointer` (see
+    /// [`get_thread_pointer`](crate::process::tls::get_thread_pointer)).
+    ///
+    /// This uses the static TLS layout of the C library, which covers thread local variables in
+    /// the binary and in a library that was loaded at startup. The layout follows the order
+    /// the modules were loaded in, so this fails for symbols in the library when the
+    /// [`link_map`](ProcessInfo::link_map) list of the dynamic linker couldn't be read.
+    pub fn get_tls_symbol_address(&self, symbol: &str, thread_pointer: u64) -> Result<u64, Error> {
+        let (source, sym) = self
+            .lookup_symbol(symbol)
+            .ok_or_else(|| format_err!("Failed to find symbol {}", symbol))?;
+        if sym.kind != SymbolKind::Tls {
+            return Err(format_err!("{} is not a thread local symbol", symbol));
+        }
+        self.get_tls_address(source, sym, thread_pointer)
+    }
+
+    /// Like [`get_tls_symbol_address`](ProcessInfo::get_tls_symbol_address), but for a thread
+    /// local symbol that was already looked up in the module `source`. This works for symbols
+    /// in any module loaded at startup, like the ones found by
+    /// [`resolve_symbol`](ProcessInfo::resolve_symbol).
+    pub fn get_tls_address(
+        &self,
+        source: SymbolSource,
+        symbol: &Symbol,
+        thread_pointer: u64,
+    ) -> Result<u64, Error> {
+        if symbol.kind != SymbolKind::Tls {
+            return Err(format_err!(
+                "The symbol at {:#x} in {:?} is not thread local",
+                symbol.address,
+                source
+            ));
+        }
+        let arch = self.arch().unwrap_or_else(Architecture::native);
+        let variant = TlsVariant::for_arch(arch)
+            .ok_or_else(|| format_err!("Thread local storage isn't supported on {}", arch))?;
+
+        let tls = self.static_tls_modules(source)?;
+        let offsets = static_tls_offsets(&tls, variant);
+        Ok(tls_address(
+            thread_pointer,
+            offsets[offsets.len() - 1],
+            symbol.address,
+        ))
+    }
+
+    // The TLS segments of the modules that were loaded up to and including `source`, in load
+    // order and with their module ids, ending with the segment of `source`. The dynamic
+    // linker hands out module ids and lays out static TLS in the order it loaded the modules
+    // with TLS segments in, which is the order of the link_map list.
+    fn static_tls_modules(&self, source: SymbolSource) -> Result<Vec<TlsInfo>, Error> {
+        let binary_tls = || self.binary.as_ref().and_then(|binary| binary.tls.clone());
+
+        // the executable is always loaded first
+        let target = match source {
+            SymbolSource::Binary => {
+                return binary_tls()
+                    .map(|tls| vec![tls])
+                    .ok_or_else(|| format_err!("The binary doesn't have a TLS segment"))
+            }
+            SymbolSource::Library => self.library_module().ok_or_else(|| {
+                format_err!("Failed to find the library in the memory maps of the process")
+            })?,
+            SymbolSource::Module(index) => index,
+        };
+        let target_path = &self
+            .modules
+            .modules()
+            .get(target)
+            .ok_or_else(|| format_err!("There is no module {} in the process", target))?
+            .path;
+        let link_map = self.link_map.as_ref().ok_or_else(|| {
+            format_err!(
+                "Failed to find the TLS layout of {}: the link_map list of the dynamic linker \
+                 couldn't be read, so the load order of the modules isn't known",
+                target_path.display()
+            )
+        })?;
+
+        let library_module = self.library_module();
+        let mut modules = Vec::new();
+        // modules in other linker namespaces are loaded with dlmopen, after the static TLS
+        // layout is set up
+        for entry in link_map.iter().filter(|entry| entry.namespace == 0) {
+            let index = match self.modules.index_at(entry.dynamic) {
+                Some(index) => index,
+                // the vdso has a name like linux-vdso.so.1, which isn't a file
+                None if !std::path::Path::new(&entry.name).is_absolute() => continue,
+                None => {
+                    return Err(format_err!(
+                        "Failed to find the TLS layout of {}: {} was loaded before it, but \
+                         isn't in the memory maps",
+                        target_path.display(),
+                        entry.name
+                    ))
+                }
+            };
+            let module = &self.modules.modules()[index];
+            let tls = if Some(index) == library_module {
+                self.library
+                    .as_ref()
+                    .and_then(|library| library.tls.clone())
+            } else if module.is_bin {
+                binary_tls().or_else(|| module.tls().cloned())
+            } else {
+                module.tls().cloned()
+            };
+            let has_tls = tls.is_some();
+            if let Some(tls) = tls {
+                let module_id = modules.len() as u64 + 1;
+                modules.push(TlsInfo {
+                    module_id: Some(module_id),
+                    ..tls
+                });
+            }
+            if index == target {
+                if !has_tls {
+                    return Err(format_err!(
+                        "{} doesn't have a TLS segment",
+                        target_path.display()
+                    ));
+                }
+                return Ok(modules);
+            }
+        }
+        Err(format_err!(
+            "Failed to find the TLS layout of {}: it isn't in the link_map list of the dynamic \
+             linker",
+            target_path.display()
+        ))
+    }
+
+    /// Returns the name of the function or object containing `addr`, along with the offset
+    /// of `addr` inside of it. The lookup is done in whichever of the binary or the library
+    /// contains `addr` in its executable mapping or in one of its sections.
+    pub fn symbolize(&self, addr: u64) -> Option<(&str, u64)> {
+        [&self.binary, &self.library]
+            .iter()
+            .filter_map(|module| module.as_ref())
+            .find(|module| module.contains(addr) || module.section_containing(addr).is_some())
+            .and_then(|module| module.symbolize(addr))
+    }
+}
+
+/// Where in the process a symbol was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolSource {
+    /// The main binary of the process
+    Binary,
+    /// The runtime's shared library (libpython, libruby etc)
+    Library,
+    /// Another module of the process, by its index in [`ModuleMap::modules`]
+    Module(usize),
+}
+
+/// A symbol declared by a `ProcessType` that was found in the process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundSymbol {
+    /// The name of the symbol
+    pub name: String,
+    /// The module the symbol was found in
+    pub source: SymbolSource,
+    /// The address of the symbol in the process
+    pub address: u64,
+}
+
+/// Which of the symbols declared by a `ProcessType` were found, and where
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolReport {
+    /// The symbols that were found, required ones first
+    pub found: Vec<FoundSymbol>,
+    /// The required symbols that couldn't be found
+    pub missing_required: Vec<String>,
+    /// The optional symbols that couldn't be found
+    pub missing_optional: Vec<String>,
+}
+
+impl SymbolReport {
+    /// Gets where the named symbol was found, if it was
+    pub fn get(&self, name: &str) -> Option<&FoundSymbol> {
+        self.found.iter().find(|symbol| symbol.name == name)
+    }
+}
+
+impl std::fmt::Display for SymbolReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "found {} symbols", self.found.len())?;
+        for symbol in &self.found {
+            write!(
+                f,
+                ", {} (0x{:016x}) in {:?}",
+                symbol.name, symbol.address, symbol.source
+            )?;
+        }
+        if !self.missing_required.is_empty() {
+            write!(f, "; missing required {}", self.missing_required.join(", "))?;
+        }
+        if !self.missing_optional.is_empty() {
+            write!(f, "; missing optional {}", self.missing_optional.join(", "))?;
+        }
+        Ok(())
     }
 }
 
+/// The error returned by [`ProcessInfo::new`] when required symbols are missing. The full
+/// report can be recovered with `error.downcast_ref::<MissingSymbolsError>()`.
+#[derive(Debug, Clone)]
+pub struct MissingSymbolsError {
+    /// The path to the binary of the process
+    pub path: PathBuf,
+    /// Where the required symbols were found, and which of them are missing
+    pub report: SymbolReport,
+}
+
+impl std::fmt::Display for MissingSymbolsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to find required symbols {} for {}",
+            self.report.missing_required.join(", "),
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for MissingSymbolsError {}
+
+/// Returns the CPU architecture of a process where it can differ from the architecture that
+/// spytools was compiled for, like x86_64 processes running under Rosetta on arm64 macs
+#[cfg(target_os = "macos")]
+fn process_arch(_procfs: &Procfs, pid: remoteprocess::Pid) -> Option<Architecture> {
+    let name = std::ffi::CString::new("sysctl.proc_cputype").unwrap();
+    let mut mib = [0 as libc::c_int; 12];
+    // leave room for the pid, which is the last component of the name
+    let mut len = mib.len() - 1;
+    if unsafe { libc::sysctlnametomib(name.as_ptr(), mib.as_mut_ptr(), &mut len) } != 0 {
+        warn!("Failed to look up sysctl.proc_cputype");
+        return None;
+    }
+    mib[len] = pid;
+
+    let mut cputype: libc::cpu_type_t = 0;
+    let mut size = std::mem::size_of::<libc::cpu_type_t>();
+    let ret = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            (len + 1) as libc::c_uint,
+            &mut cputype as *mut _ as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ret != 0 {
+        warn!(
+            "Failed to get the cpu type of process {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+
+    // arm64 and arm64e processes have the same cpu type, and are told apart by the subtype
+    // from PROC_PIDARCHINFO, which fills in a struct proc_archinfo { cpu_type_t p_cputype;
+    // cpu_subtype_t p_cpusubtype; }
+    const PROC_PIDARCHINFO: libc::c_int = 19;
+    let mut archinfo: [libc::c_int; 2] = [0; 2];
+    let size = std::mem::size_of_val(&archinfo) as libc::c_int;
+    let ret = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            PROC_PIDARCHINFO,
+            0,
+            archinfo.as_mut_ptr() as *mut libc::c_void,
+            size,
+        )
+    };
+    let cpusubtype = if ret == size {
+        archinfo[1] as u32
+    } else {
+        debug!("Failed to get the cpu subtype of process {}", pid);
+        0
+    };
+    Some(Architecture::from_mach_cputype(cputype as u32, cpusubtype))
+}
+
+/// Returns the CPU architecture of a process from the ELF header of its executable, which
+/// differs from the architecture spytools was compiled for for 32 bit processes
 #[cfg(target_os = "linux")]
-fn is_dockerized(pid: remoteprocess::Pid) -> Result<bool, Error> {
-    let self_mnt = std::fs::read_link("/proc/self/ns/mnt")?;
-    let target_mnt = std::fs::read_link(&format!("/proc/{}/ns/mnt", pid))?;
-    Ok(self_mnt != target_mnt)
+fn process_arch(procfs: &Procfs, pid: remoteprocess::Pid) -> Option<Architecture> {
+    use std::io::Read;
+
+    let mut header = [0; goblin::elf::header::header64::SIZEOF_EHDR];
+    let mut file = std::fs::File::open(procfs.process_path(pid, "exe")).ok()?;
+    file.read_exact(&mut header).ok()?;
+    match goblin::elf::Elf::parse_header(&header) {
+        Ok(header) => Some(Architecture::from_elf_machine(
+            header.e_machine,
+            header.e_ident[goblin::elf::header::EI_CLASS] == goblin::elf::header::ELFCLASS64,
+        )),
+        Err(e) => {
+            warn!("Failed to read the ELF header of process {}: {}", pid, e);
+            None
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn process_arch(_procfs: &Procfs, _pid: remoteprocess::Pid) -> Option<Architecture> {
+    None
+}
+
+/// Finds the executable memory map of the main binary of a process, whose executable is at
+/// `filename`.
+///
+/// Maps are matched by the device and inode of `/proc/<pid>/exe` where those are known, which
+/// finds the binary when its path in the maps differs from the path of the executable (like
+/// for symlinked interpreters, overlayfs or paths from another mount namespace). Otherwise
+/// they're matched by path with `is_bin`.
+fn find_binary_map<'a, F>(
+    procfs: &Procfs,
+    pid: remoteprocess::Pid,
+    filename: &str,
+    maps: &'a [MemoryMap],
+    is_bin: F,
+) -> Option<&'a MemoryMap>
+where
+    F: Fn(&str) -> bool,
+{
+    fn map_path(map: &MemoryMap) -> Option<&str> {
+        map.path.as_deref().and_then(|path| path.to_str())
+    }
+    fn map_file_id(map: &MemoryMap) -> Option<((u32, u32), u64)> {
+        Some((map.device?, map.inode?))
+    }
+    let executable = || {
+        maps.iter()
+            .filter(|map| map.executable && map.path.is_some())
+    };
+    let exe_id = exe_file_id(procfs, pid);
+
+    if let Some(exe_id) = exe_id {
+        let matches: Vec<&MemoryMap> = executable()
+            .filter(|map| map_file_id(map) == Some(exe_id))
+            .collect();
+        let mut paths: Vec<&str> = matches.iter().filter_map(|map| map_path(map)).collect();
+        paths.dedup();
+        if paths.len() > 1 {
+            warn!(
+                "Found several files with the device and inode of the executable {} of process {}: {}",
+                filename,
+                pid,
+                paths.join(", ")
+            );
+        }
+        let map = matches
+            .iter()
+            .find(|map| map_path(map).is_some_and(&is_bin))
+            .or_else(|| matches.first());
+        if let Some(map) = map {
+            if let Some(path) = map_path(map).filter(|path| !is_bin(path)) {
+                info!(
+                    "Found executable {} of process {} mapped as {}",
+                    filename, pid, path
+                );
+            }
+            return Some(map);
+        }
+        debug!(
+            "No memory map has the device and inode of the executable of process {}",
+            pid
+        );
+    }
+
+    let map = executable().find(|map| map_path(map).is_some_and(&is_bin));
+    if map.is_none() {
+        let candidates: Vec<String> = executable()
+            .map(|map| {
+                let path = map.path.as_ref().unwrap().display();
+                match map_file_id(map) {
+                    Some(((major, minor), inode)) => {
+                        format!("{} ({:02x}:{:02x} inode {})", path, major, minor, inode)
+                    }
+                    None => path.to_string(),
+                }
+            })
+            .collect();
+        warn!(
+            "Failed to match the executable {} of process {} (device and inode {}) with the executable memory maps: {}",
+            filename,
+            pid,
+            match exe_id {
+                Some(((major, minor), inode)) => format!("{:02x}:{:02x} {}", major, minor, inode),
+                None => "unknown".to_string(),
+            },
+            candidates.join(", ")
+        );
+    }
+    map
+}
+
+/// Returns the device (major and minor numbers) and inode of the executable of a process
+#[cfg(target_os = "linux")]
+fn exe_file_id(procfs: &Procfs, pid: remoteprocess::Pid) -> Option<((u32, u32), u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::metadata(procfs.process_path(pid, "exe")).ok()?;
+    let dev = metadata.dev();
+    Some(((libc::major(dev), libc::minor(dev)), metadata.ino()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exe_file_id(_procfs: &Procfs, _pid: remoteprocess::Pid) -> Option<((u32, u32), u64)> {
+    None
 }
 
 #[cfg(target_os = "windows")]
@@ -302,6 +1066,15 @@ where
     Ok(ret)
 }
 
+#[cfg(target_os = "windows")]
+fn windows_symbols(symbols: HashMap<String, u64>) -> impl Iterator<Item = (String, Symbol)> {
+    use crate::binary_parser::SymbolOrigin;
+
+    symbols
+        .into_iter()
+        .map(|(name, addr)| (name, Symbol::new(addr, SymbolOrigin::Pdb)))
+}
+
 /// Returns `true` if the file at `path` looks like a library, and false otherwise.
 pub fn is_lib<T>(path: &std::path::Path) -> bool
 where
@@ -309,3 +1082,242 @@ where
 {
     T::library_regex().is_match(&path.to_string_lossy())
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_find_binary_map() {
+        use super::*;
+
+        // the executable is found by its device and inode when its path doesn't match, like
+        // for paths from another mount namespace
+        let pid = std::process::id() as remoteprocess::Pid;
+        let procfs = Procfs::default();
+        let maps = procfs.maps(pid).unwrap();
+        let map = find_binary_map(&procfs, pid, "/usr/bin/python3", &maps, |_| false).unwrap();
+        assert!(map.executable);
+        assert_eq!(
+            map.path.as_deref().unwrap(),
+            std::env::current_exe().unwrap().canonicalize().unwrap()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_info_with_procfs() {
+        use super::*;
+        use crate::binary_parser::tests::fixture;
+        use crate::process::container::ContainerRuntime;
+        use std::os::unix::fs::{symlink, MetadataExt};
+        use std::path::Path;
+
+        struct FixtureProcessType {}
+        impl ProcessType for FixtureProcessType {
+            fn required_symbols() -> Vec<String> {
+                vec!["fixture_global_data".to_string()]
+            }
+            fn library_regex() -> regex::Regex {
+                regex::Regex::new(r"/libfixture\.stripped$").unwrap()
+            }
+        }
+
+        // a proc tree for a process that isn't running, with the nopie fixture as its
+        // executable and libfixture as its library, in a container
+        let (exe, lib) = (fixture("fixture.nopie"), fixture("libfixture.stripped"));
+        let root = std::env::temp_dir().join(format!("spytools-procfs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let pid: remoteprocess::Pid = 2_000_000_000;
+        let dir = root.join(pid.to_string());
+        std::fs::create_dir_all(dir.join("ns")).unwrap();
+        std::fs::create_dir_all(root.join("self/ns")).unwrap();
+        symlink(&exe, dir.join("exe")).unwrap();
+        symlink("/", dir.join("root")).unwrap();
+        symlink("mnt:[4026532731]", dir.join("ns/mnt")).unwrap();
+        symlink("pid:[4026531836]", dir.join("ns/pid")).unwrap();
+        symlink("mnt:[4026531841]", root.join("self/ns/mnt")).unwrap();
+        symlink("pid:[4026531836]", root.join("self/ns/pid")).unwrap();
+        let id = "3f2b1c0d9e8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c";
+        std::fs::write(
+            dir.join("cgroup"),
+            format!("0::/system.slice/docker-{}.scope\n", id),
+        )
+        .unwrap();
+        std::fs::write(dir.join("status"), format!("NSpid:\t{}\t1\n", pid)).unwrap();
+
+        let map = |start: u64, end: u64, perms: &str, offset: u64, path: &Path| {
+            let metadata = std::fs::metadata(path).unwrap();
+            let dev = metadata.dev();
+            format!(
+                "{:x}-{:x} {} {:08x} {:02x}:{:02x} {} {}\n",
+                start,
+                end,
+                perms,
+                offset,
+                libc::major(dev),
+                libc::minor(dev),
+                metadata.ino(),
+                path.display()
+            )
+        };
+        let maps = [
+            map(0x400000, 0x401000, "r--p", 0, &exe),
+            map(0x401000, 0x402000, "r-xp", 0x1000, &exe),
+            map(0x402000, 0x403000, "r--p", 0x2000, &exe),
+            map(0x403000, 0x405000, "rw-p", 0x2000, &exe),
+            map(0x7f0000000000, 0x7f0000001000, "r--p", 0, &lib),
+            map(0x7f0000001000, 0x7f0000002000, "r-xp", 0x1000, &lib),
+            map(0x7f0000002000, 0x7f0000003000, "r--p", 0x2000, &lib),
+            map(0x7f0000003000, 0x7f0000005000, "rw-p", 0x2000, &lib),
+            "7ffd1c9d4000-7ffd1c9f5000 rw-p 00000000 00:00 0   [stack]\n".to_string(),
+        ];
+        std::fs::write(dir.join("maps"), maps.concat()).unwrap();
+
+        let process = remoteprocess::Process::new(pid).unwrap();
+        let process_info =
+            ProcessInfo::with_procfs::<FixtureProcessType>(&process, &Procfs::new(&root));
+        std::fs::remove_dir_all(&root).unwrap();
+        let process_info = process_info.unwrap();
+
+        assert_eq!(process_info.path, exe);
+        // the maps come from the fake proc tree rather than from /proc
+        assert_eq!(process_info.memory_maps.len(), 9);
+        assert!(process_info.maps_contain_addr(0x404004));
+        assert!(!process_info.maps_contain_addr(0x405000));
+        // the maps of the library are adjacent, unlike the ones of the library and the stack
+        assert!(process_info.maps_contain_addr_range(0x7f0000000800, 0x4000));
+        assert!(!process_info.maps_contain_addr_range(0x7f0000004800, 0x1000));
+        assert!(!process_info.maps_contain_addr_range(0x7f0000000800, 0));
+        let binary = process_info.binary.as_ref().unwrap();
+        assert_eq!(
+            (binary.filename.as_path(), binary.load_bias),
+            (dir.join("exe").as_path(), 0)
+        );
+        let library = process_info.library.as_ref().unwrap();
+        assert_eq!(library.load_bias, 0x7f0000000000);
+        assert_eq!(
+            library.filename,
+            dir.join("root").join(lib.strip_prefix("/").unwrap())
+        );
+        assert_eq!(
+            process_info.get_symbol("fixture_global_data"),
+            Some(&0x404004)
+        );
+        assert_eq!(process_info.modules.modules().len(), 2);
+        assert_eq!(process_info.modules.binary().unwrap().path, exe);
+        assert_eq!(process_info.arch(), Some(Architecture::X86_64));
+
+        let container = &process_info.container;
+        assert!(container.other_mount_namespace && !container.other_pid_namespace);
+        assert_eq!(container.namespace_pid, Some(1));
+        assert_eq!(container.container_id.as_deref(), Some(id));
+        assert_eq!(container.runtime, Some(ContainerRuntime::Docker));
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn test_symbol_report() {
+        use super::*;
+
+        // treat libc as the runtime library, so that the lookups are made in a known library
+        struct LibcProcessType {}
+        impl ProcessType for LibcProcessType {
+            fn required_symbols() -> Vec<String> {
+                vec!["malloc".to_string()]
+            }
+            fn optional_symbols() -> Vec<String> {
+                vec!["free".to_string(), "spytools_missing".to_string()]
+            }
+            fn library_regex() -> regex::Regex {
+                regex::Regex::new(r"/libc\.so\.6$").unwrap()
+            }
+        }
+
+        struct MissingProcessType {}
+        impl ProcessType for MissingProcessType {
+            fn required_symbols() -> Vec<String> {
+                vec!["malloc".to_string(), "spytools_missing".to_string()]
+            }
+            fn library_regex() -> regex::Regex {
+                LibcProcessType::library_regex()
+            }
+        }
+
+        let process =
+            remoteprocess::Process::new(std::process::id() as remoteprocess::Pid).unwrap();
+        let process_info = ProcessInfo::new::<LibcProcessType>(&process).unwrap();
+        let report = process_info.check_symbols::<LibcProcessType>();
+        let names: Vec<&str> = report.found.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["malloc", "free"]);
+        assert_eq!(report.get("malloc").unwrap().source, SymbolSource::Library);
+        assert_eq!(
+            report.get("malloc").unwrap().address,
+            libc::malloc as *const () as u64
+        );
+        assert!(report.missing_required.is_empty());
+        assert_eq!(report.missing_optional, vec!["spytools_missing"]);
+        assert!(process_info.maps_contain_addr(libc::malloc as *const () as u64));
+        assert_eq!(process_info.arch(), Some(Architecture::native()));
+        assert_eq!(
+            process_info.pointer_size(),
+            Some(std::mem::size_of::<usize>())
+        );
+
+        let err = match ProcessInfo::new::<MissingProcessType>(&process) {
+            Ok(_) => panic!("expected missing symbols to be reported"),
+            Err(err) => err,
+        };
+        let err = err.downcast_ref::<MissingSymbolsError>().unwrap();
+        assert_eq!(err.report.missing_required, vec!["spytools_missing"]);
+        assert_eq!(err.report.found.len(), 1);
+
+        let (name, source, _) = process_info
+            .lookup_first(&["spytools_missing", "free", "malloc"])
+            .unwrap();
+        assert_eq!((name, source), ("free", SymbolSource::Library));
+        let regex = regex::Regex::new(r"^(malloc|calloc)$").unwrap();
+        let mut names: Vec<&str> = process_info
+            .find_symbols(&regex)
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names, vec!["calloc", "malloc"]);
+
+        // symbols outside of the binary and the library are only resolved on request, from
+        // the other modules in load order starting from the binary
+        let order: Vec<&crate::process::Module> = process_info.modules.search_order().collect();
+        assert!(order[0].is_bin);
+        assert_eq!(order.len(), process_info.modules.modules().len());
+        assert!(process_info.get_symbol("_dl_debug_state").is_none());
+        let (source, _) = process_info.resolve_symbol("_dl_debug_state").unwrap();
+        let index = match source {
+            SymbolSource::Module(index) => index,
+            source => panic!("_dl_debug_state found in {:?}", source),
+        };
+        let name = process_info.modules.modules()[index].name().unwrap();
+        assert!(name.starts_with("ld-linux"), "{}", name);
+        let (source, _) = process_info
+            .resolve_symbol("__libc_single_threaded")
+            .unwrap();
+        assert_eq!(source, SymbolSource::Library);
+
+        // the dynamic linker's view of the modules agrees with the memory maps
+        let link_map = process_info.link_map.as_ref().unwrap();
+        assert_eq!(link_map[0].name, "");
+        assert_eq!(
+            link_map[0].load_bias,
+            process_info.binary.as_ref().unwrap().load_bias
+        );
+        let libc = link_map
+            .iter()
+            .find(|entry| entry.name.ends_with("/libc.so.6"))
+            .unwrap();
+        assert_eq!(
+            libc.load_bias,
+            process_info.library.as_ref().unwrap().load_bias
+        );
+        assert!(link_map.iter().all(|entry| entry.namespace == 0));
+    }
+}
@@ -1,8 +1,23 @@
 /// An abstraction over the different language runtimes (Python, Ruby, etc) that we support
 pub trait ProcessType {
+    /// Symbols that have to be found in the binary or library for the process to be inspected.
+    /// [`ProcessInfo::new`](crate::ProcessInfo::new) fails if any of these are missing.
+    fn required_symbols() -> Vec<String> {
+        Vec::new()
+    }
+    /// Symbols that are used if they exist, like ones that are only present in some versions
+    /// of the runtime
+    fn optional_symbols() -> Vec<String> {
+        Vec::new()
+    }
     #[cfg(target_os = "windows")]
-    /// Returns all symbols available in the process
-    fn windows_symbols() -> Vec<String>;
+    /// Returns the symbols to load from the separate debug files (.pdb) of the process.
+    /// Defaults to the required and optional symbols.
+    fn windows_symbols() -> Vec<String> {
+        let mut symbols = Self::required_symbols();
+        symbols.extend(Self::optional_symbols());
+        symbols
+    }
     /// A regular expression that matches library filenames for this process type
     fn library_regex() -> regex::Regex;
     /// Returns `true` if the given filename looks like a macOS framework, and `false` otherwise
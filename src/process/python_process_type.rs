@@ -6,8 +6,8 @@ use crate::process::ProcessType;
 pub struct PythonProcessType {}
 
 impl ProcessType for PythonProcessType {
-    #[cfg(windows)]
-    fn windows_symbols() -> Vec<String> {
+    fn optional_symbols() -> Vec<String> {
+        // which of these exist depends on the python version, so none of them are required
         vec![
             "_PyThreadState_Current".to_string(),
             "interp_head".to_string(),
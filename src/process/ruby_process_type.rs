@@ -6,8 +6,12 @@ use crate::process::ProcessType;
 pub struct RubyProcessType {}
 
 impl ProcessType for RubyProcessType {
-    #[cfg(windows)]
-    fn windows_symbols() -> Vec<String> {
+    fn required_symbols() -> Vec<String> {
+        vec!["ruby_version".to_string()]
+    }
+
+    fn optional_symbols() -> Vec<String> {
+        // which of these exist depends on the ruby version
         vec![
             "global_symbols".to_string(),
             "ruby_global_symbols".to_string(),
@@ -15,7 +19,6 @@ impl ProcessType for RubyProcessType {
             "ruby_current_vm_ptr".to_string(),
             "ruby_current_thread".to_string(),
             "ruby_current_execution_context_ptr".to_string(),
-            "ruby_version".to_string(),
         ]
     }
 
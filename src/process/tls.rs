@@ -0,0 +1,363 @@
+use anyhow::{format_err, Error};
+
+use crate::binary_parser::{Architecture, TlsInfo};
+
+/// How the C library places the static thread local storage blocks of the modules that were
+/// loaded at startup, relative to the thread pointer (`fs_base` on x86_64, `tpidr_el0` on
+/// aarch64). glibc and musl use the same variant for a given architecture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsVariant {
+    /// TLS variant I: blocks are placed in module order after the thread pointer,
+    /// following a thread control block of the given size (aarch64, arm, riscv)
+    AboveThreadPointer {
+        /// The size of the thread control block between the thread pointer and the first block
+        tcb_size: u64,
+    },
+    /// TLS variant II: blocks are placed in module order before the thread pointer (x86, x86_64)
+    BelowThreadPointer,
+}
+
+impl TlsVariant {
+    /// Returns the layout used on the architecture spytools was compiled for
+    pub fn native() -> Option<TlsVariant> {
+        TlsVariant::for_arch(Architecture::native())
+    }
+
+    /// Returns the layout used on an architecture, which can differ from the one spytools was
+    /// compiled for (like 32 bit arm processes on aarch64)
+    pub fn for_arch(arch: Architecture) -> Option<TlsVariant> {
+        match arch {
+            Architecture::X86 | Architecture::X86_64 => Some(TlsVariant::BelowThreadPointer),
+            Architecture::Aarch64 | Architecture::Arm64e => {
+                Some(TlsVariant::AboveThreadPointer { tcb_size: 16 })
+            }
+            Architecture::Arm => Some(TlsVariant::AboveThreadPointer { tcb_size: 8 }),
+            Architecture::RiscV => Some(TlsVariant::AboveThreadPointer { tcb_size: 0 }),
+            _ => None,
+        }
+    }
+}
+
+/// Computes the offset from the thread pointer to the static TLS block of each module.
+///
+/// `modules` are the TLS segments of the modules loaded at startup in the order the dynamic
+/// linker loaded them (which is the order of their TLS module ids). This follows glibc's
+/// `_dl_determine_tlsoffset`: each block is placed at the first suitably aligned position past
+/// the previous one, except that glibc remembers the largest gap that aligning a block left
+/// behind and places later blocks that fit in it there. musl never reuses these gaps, so for a
+/// musl process the offsets are only right when no block fits in an earlier gap. Modules
+/// loaded later with `dlopen` use dynamically allocated TLS and aren't covered by this.
+pub fn static_tls_offsets(modules: &[TlsInfo], variant: TlsVariant) -> Vec<i64> {
+    // blocks are aligned so that their start has the same misalignment as the image
+    // address (to keep addresses that the linker computed for TLS relative data valid)
+    let misalignment = |module: &TlsInfo| module.image_addr & (module.align.max(1) - 1);
+    let align_mask = |module: &TlsInfo| module.align.max(1) - 1;
+
+    let mut offsets = Vec::with_capacity(modules.len());
+    // the largest gap left between blocks so far, as distances from the thread pointer
+    let (mut gap_start, mut gap_end) = (0u64, 0u64);
+    match variant {
+        TlsVariant::BelowThreadPointer => {
+            // blocks end at thread_pointer - offset of the previous block
+            let mut offset: u64 = 0;
+            for module in modules {
+                // block start is thread_pointer - offset, which needs to be congruent to
+                // the image address modulo the alignment
+                let wanted = misalignment(module).wrapping_neg() & align_mask(module);
+                let place = |after: u64| {
+                    let end = after + module.size;
+                    end + (wanted.wrapping_sub(end) & align_mask(module))
+                };
+
+                if gap_end - gap_start >= module.size {
+                    let start = place(gap_start);
+                    if start <= gap_end {
+                        gap_start = start;
+                        offsets.push(-(start as i64));
+                        continue;
+                    }
+                }
+
+                let start = place(offset);
+                if start - module.size - offset > gap_end - gap_start {
+                    gap_start = offset;
+                    gap_end = start - module.size;
+                }
+                offset = start;
+                offsets.push(-(offset as i64));
+            }
+        }
+        TlsVariant::AboveThreadPointer { tcb_size } => {
+            let mut offset = tcb_size;
+            for module in modules {
+                let place = |after: u64| {
+                    after + (misalignment(module).wrapping_sub(after) & align_mask(module))
+                };
+
+                if gap_end - gap_start >= module.size {
+                    let start = place(gap_start);
+                    if start + module.size <= gap_end {
+                        gap_start = start + module.size;
+                        offsets.push(start as i64);
+                        continue;
+                    }
+                }
+
+                let start = place(offset);
+                if start - offset > gap_end - gap_start {
+                    gap_start = offset;
+                    gap_end = start;
+                }
+                offsets.push(start as i64);
+                offset = start + module.size;
+            }
+        }
+    }
+    offsets
+}
+
+/// Returns the address of a thread local variable for a thread, given the TLS segment of the
+/// module defining it, the offset of the variable in the module's block (the value of the TLS
+/// symbol), and the static TLS offset of the module from [`static_tls_offsets`].
+pub fn tls_address(thread_pointer: u64, module_offset: i64, symbol_offset: u64) -> u64 {
+    thread_pointer
+        .wrapping_add(module_offset as u64)
+        .wrapping_add(symbol_offset)
+}
+
+/// Reads the thread pointer (`fs_base` on x86_64, `tpidr_el0` on aarch64) of a thread in
+/// another process. The thread must be stopped under ptrace, for example by holding the lock
+/// returned by `remoteprocess::Process::lock`.
+///
+/// This reads the register used by 64 bit processes, so it doesn't work for 32 bit x86
+/// processes, which use a `gs` segment for their thread pointer.
+#[cfg(target_os = "linux")]
+pub fn get_thread_pointer(tid: remoteprocess::Tid) -> Result<u64, Error> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            libc::ptrace(
+                libc::PTRACE_GETREGS,
+                tid,
+                std::ptr::null_mut::<libc::c_void>(),
+                &mut regs as *mut _ as *mut libc::c_void,
+            )
+        };
+        if ret != 0 {
+            return Err(format_err!(
+                "Failed to get registers of thread {}: {}",
+                tid,
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(regs.fs_base)
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        const NT_ARM_TLS: libc::c_int = 0x401;
+        let mut tpidr: u64 = 0;
+        let mut iov = libc::iovec {
+            iov_base: &mut tpidr as *mut _ as *mut libc::c_void,
+            iov_len: std::mem::size_of::<u64>(),
+        };
+        let ret = unsafe {
+            libc::ptrace(
+                libc::PTRACE_GETREGSET,
+                tid,
+                NT_ARM_TLS as *mut libc::c_void,
+                &mut iov as *mut _ as *mut libc::c_void,
+            )
+        };
+        if ret != 0 {
+            return Err(format_err!(
+                "Failed to get thread pointer of thread {}: {}",
+                tid,
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(tpidr)
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        Err(format_err!(
+            "Reading the thread pointer of thread {} isn't supported on this architecture",
+            tid
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(image_addr: u64, size: u64, align: u64) -> TlsInfo {
+        TlsInfo {
+            image_addr,
+            image_size: 0,
+            size,
+            align,
+            module_id: None,
+        }
+    }
+
+    #[test]
+    fn test_static_tls_offsets_below_thread_pointer() {
+        let modules = [
+            module(0x5ce240, 0xa0, 8),
+            module(0x1cf8d0, 0x90, 8),
+            module(0x3dc4, 0x8, 4),
+            // image at 0x10 misaligned for 64: the block has to keep that misalignment
+            module(0x2010, 0x20, 64),
+        ];
+        let offsets = static_tls_offsets(&modules, TlsVariant::BelowThreadPointer);
+        assert_eq!(offsets[..3], [-0xa0, -0x130, -0x138]);
+        // -0x138 - 0x20 = -0x158, the next block starting at -x with -x = 0x10 mod 64
+        assert_eq!(offsets[3], -0x170);
+        assert_eq!(offsets[3].rem_euclid(64), 0x10);
+    }
+
+    #[test]
+    fn test_static_tls_offsets_reuse_gaps() {
+        // the TLS segments of an executable and four libraries with mixed alignments, laid
+        // out by glibc 2.36 on x86_64 at the offsets below
+        let modules = [
+            module(0x3d78, 0x8, 8),
+            module(0x3e40, 0x10, 64),
+            module(0x3e20, 0x30, 16),
+            module(0x3e4c, 0x4, 4),
+            module(0x3e30, 0x20, 16),
+        ];
+        let offsets = static_tls_offsets(&modules, TlsVariant::BelowThreadPointer);
+        // the second block leaves a gap at 0x8..0x30, which is too small for the third block,
+        // but takes the fourth block and then the fifth one after it
+        assert_eq!(offsets, vec![-0x8, -0x40, -0x70, -0xc, -0x30]);
+
+        // only the largest gap is kept: the one at 0x0..0x8 in front of the first block is
+        // forgotten once the second block leaves one at 0x10..0x30
+        let modules = [
+            module(0x1000, 0x8, 16),
+            module(0x2000, 0x10, 64),
+            module(0x3000, 0x8, 8),
+        ];
+        let offsets = static_tls_offsets(&modules, TlsVariant::BelowThreadPointer);
+        assert_eq!(offsets, vec![-0x10, -0x40, -0x18]);
+    }
+
+    #[test]
+    fn test_static_tls_offsets_above_thread_pointer() {
+        let modules = [
+            module(0x1000, 0x14, 8),
+            module(0x2000, 0x8, 64),
+            module(0x3004, 0x4, 8),
+        ];
+        let offsets = static_tls_offsets(&modules, TlsVariant::AboveThreadPointer { tcb_size: 16 });
+        // aligning the second block leaves a gap at 36..64, which the last block fits in
+        assert_eq!(offsets, vec![16, 64, 36]);
+
+        // 32 bit arm processes running on aarch64 use a smaller thread control block
+        assert_eq!(
+            TlsVariant::for_arch(Architecture::Arm),
+            Some(TlsVariant::AboveThreadPointer { tcb_size: 8 })
+        );
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_get_thread_pointer() {
+        use remoteprocess::ProcessMemory;
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let pid = child.id() as remoteprocess::Pid;
+        // give the child time to exec and set up its thread pointer
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let process = remoteprocess::Process::new(pid).unwrap();
+        let thread_pointer = {
+            let _lock = process.lock().unwrap();
+            get_thread_pointer(pid).unwrap()
+        };
+
+        // the thread control block starts with a pointer to itself on x86_64
+        let tcb: u64 = process.copy_struct(thread_pointer as usize).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_ne!(thread_pointer, 0);
+        assert_eq!(tcb, thread_pointer);
+    }
+
+    #[cfg(all(
+        target_os = "linux",
+        target_env = "gnu",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[test]
+    fn test_get_tls_symbol_address() {
+        use crate::process::{ProcessType, SymbolSource};
+        use crate::ProcessInfo;
+
+        // treat libc as the runtime library, since it has thread local variables
+        struct LibcProcessType {}
+        impl ProcessType for LibcProcessType {
+            fn library_regex() -> regex::Regex {
+                regex::Regex::new(r"/libc\.so\.6$").unwrap()
+            }
+        }
+
+        let process =
+            remoteprocess::Process::new(std::process::id() as remoteprocess::Pid).unwrap();
+        let mut process_info = ProcessInfo::new::<LibcProcessType>(&process).unwrap();
+
+        let thread_pointer: u64;
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            std::arch::asm!("mov {}, qword ptr fs:0", out(reg) thread_pointer)
+        };
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            std::arch::asm!("mrs {}, tpidr_el0", out(reg) thread_pointer)
+        };
+
+        let errno = process_info
+            .get_tls_symbol_address("errno", thread_pointer)
+            .unwrap();
+        assert_eq!(errno, unsafe { libc::__errno_location() } as u64);
+
+        assert!(process_info
+            .get_tls_symbol_address("malloc", thread_pointer)
+            .is_err());
+
+        // the test binary has thread locals of its own, so libc comes after it
+        let library_tls = process_info.library.as_ref().unwrap().tls.as_ref().unwrap();
+        assert!(library_tls.module_id > Some(1));
+
+        // thread locals in modules other than the binary and the library
+        struct NoLibraryProcessType {}
+        impl ProcessType for NoLibraryProcessType {
+            fn library_regex() -> regex::Regex {
+                regex::Regex::new(r"^$").unwrap()
+            }
+        }
+        let no_library = ProcessInfo::new::<NoLibraryProcessType>(&process).unwrap();
+        assert!(no_library
+            .get_tls_symbol_address("errno", thread_pointer)
+            .is_err());
+        let (source, symbol) = no_library.resolve_symbol("errno").unwrap();
+        assert!(matches!(source, SymbolSource::Module(_)));
+        let errno = no_library
+            .get_tls_address(source, symbol, thread_pointer)
+            .unwrap();
+        assert_eq!(errno, unsafe { libc::__errno_location() } as u64);
+
+        // without the load order, the layout of the library's TLS isn't guessed
+        process_info.link_map = None;
+        assert!(process_info
+            .get_tls_symbol_address("errno", thread_pointer)
+            .is_err());
+    }
+}
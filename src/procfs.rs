@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+
+use crate::process::MemoryMap;
+
+/// Where the proc filesystem that describes processes is mounted.
+///
+/// This is `/proc` by default, but can be somewhere else, like `/host/proc` in a sidecar
+/// container that has the host's proc filesystem mounted, or a directory laid out like
+/// `/proc` in tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Procfs {
+    root: PathBuf,
+}
+
+impl Default for Procfs {
+    fn default() -> Procfs {
+        Procfs::new("/proc")
+    }
+}
+
+impl Procfs {
+    /// Returns the proc filesystem mounted at `root`
+    pub fn new<P: Into<PathBuf>>(root: P) -> Procfs {
+        Procfs { root: root.into() }
+    }
+
+    /// Returns the directory the proc filesystem is mounted at
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path of a file in the directory of a process, like `maps` or `ns/mnt`
+    pub fn process_path(&self, pid: remoteprocess::Pid, name: &str) -> PathBuf {
+        self.root.join(pid.to_string()).join(name)
+    }
+
+    /// Returns the path of a file in the directory of the process that spytools runs in
+    pub fn self_path(&self, name: &str) -> PathBuf {
+        self.root.join("self").join(name)
+    }
+
+    /// Returns the path of the executable of a process, from the `exe` link
+    pub fn exe(&self, pid: remoteprocess::Pid) -> std::io::Result<PathBuf> {
+        std::fs::read_link(self.process_path(pid, "exe"))
+    }
+
+    /// Reads the memory maps of a process from its `maps` file
+    pub fn maps(&self, pid: remoteprocess::Pid) -> std::io::Result<Vec<MemoryMap>> {
+        let contents = std::fs::read_to_string(self.process_path(pid, "maps"))?;
+        contents
+            .lines()
+            .map(|line| {
+                parse_map(line).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Failed to parse memory map '{}' of {}", line, pid),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Parses a line of a `maps` file, like
+/// `7f2c4a5e1000-7f2c4a5e3000 r-xp 00002000 fd:01 1234   /usr/lib/libc.so.6`
+fn parse_map(line: &str) -> Option<MemoryMap> {
+    let mut fields = line.splitn(6, ' ');
+    let (start, end) = fields.next()?.split_once('-')?;
+    let perms = fields.next()?.as_bytes();
+    let offset = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let device = fields.next()?;
+    let inode: u64 = fields.next()?.parse().ok()?;
+    // the path is padded to line up, and is missing for anonymous maps
+    let path = fields
+        .next()
+        .map(str::trim_start)
+        .filter(|path| !path.is_empty());
+    let (path, deleted) = match path {
+        Some(path) => {
+            let (path, deleted) = crate::binary_parser::target::strip_deleted(Path::new(path));
+            (Some(path.to_owned()), deleted)
+        }
+        None => (None, false),
+    };
+
+    Some(MemoryMap {
+        start: u64::from_str_radix(start, 16).ok()?,
+        end: u64::from_str_radix(end, 16).ok()?,
+        file_offset: Some(offset),
+        path,
+        deleted,
+        device: parse_device(device),
+        inode: Some(inode).filter(|&inode| inode != 0),
+        readable: perms.first() == Some(&b'r'),
+        writable: perms.get(1) == Some(&b'w'),
+        executable: perms.get(2) == Some(&b'x'),
+    })
+}
+
+/// Parses a device as written in `maps`, which is the major and minor numbers in hex
+/// (like `fd:01`)
+pub(crate) fn parse_device(dev: &str) -> Option<(u32, u32)> {
+    let (major, minor) = dev.split_once(':')?;
+    Some((
+        u32::from_str_radix(major, 16).ok()?,
+        u32::from_str_radix(minor, 16).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_map() {
+        let map = parse_map(
+            "7f2c4a5e1000-7f2c4a5e3000 r-xp 00002000 fd:01 1234                       /usr/lib/libruby.so.3.2 (deleted)",
+        )
+        .unwrap();
+        assert_eq!((map.start, map.end), (0x7f2c4a5e1000, 0x7f2c4a5e3000));
+        assert_eq!(map.file_offset, Some(0x2000));
+        assert_eq!(map.path, Some(PathBuf::from("/usr/lib/libruby.so.3.2")));
+        assert!(map.deleted);
+        assert_eq!((map.device, map.inode), (Some((0xfd, 1)), Some(1234)));
+        assert!(map.readable && !map.writable && map.executable);
+
+        let map = parse_map("7ffd1c9d4000-7ffd1c9f5000 rw-p 00000000 00:00 0 ").unwrap();
+        assert_eq!((map.path, map.inode), (None, None));
+        let map = parse_map("7ffd1c9d4000-7ffd1c9f5000 rw-p 00000000 00:00 0   [stack]").unwrap();
+        assert_eq!(map.path, Some(PathBuf::from("[stack]")));
+
+        assert!(parse_map("7ffd1c9d4000 rw-p").is_none());
+    }
+}
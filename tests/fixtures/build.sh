@@ -0,0 +1,94 @@
+#!/bin/sh
+# Regenerates the binary fixtures used by the unit tests. The outputs are
+# checked in so that the tests don't need a C toolchain.
+set -eu
+cd "$(dirname "$0")"
+
+CC=${CC:-gcc}
+
+# libfixture.full: unstripped shared library with DWARF info
+$CC -g -O0 -shared -fPIC -Wl,--build-id=sha1 -o libfixture.full fixture.c
+
+# libfixture.debug + libfixture.stripped: separate debug file referenced by .gnu_debuglink
+objcopy --only-keep-debug libfixture.full libfixture.debug
+strip --strip-all -o libfixture.stripped libfixture.full
+objcopy --add-gnu-debuglink=libfixture.debug libfixture.stripped
+
+# root/: a fake filesystem root, with the debug file installed under its build-id
+build_id=$(readelf -n libfixture.full | sed -n 's/.*Build ID: \([0-9a-f]*\).*/\1/p')
+prefix=$(echo "$build_id" | cut -c1-2)
+rest=$(echo "$build_id" | cut -c3-)
+rm -rf root
+mkdir -p "root/usr/lib/debug/.build-id/$prefix"
+cp libfixture.debug "root/usr/lib/debug/.build-id/$prefix/$rest.debug"
+# and a stale debug file from another build, installed under a build-id it doesn't have
+mkdir -p root/usr/lib/debug/.build-id/00
+cp libfixture.debug root/usr/lib/debug/.build-id/00/112233445566778899aabbccddeeff00112233.debug
+
+# libfixture.minidebug: stripped library without a debuglink, carrying a MiniDebugInfo
+# (.gnu_debugdata) section with the static symbols, like Fedora/RHEL binaries
+objcopy --only-keep-debug libfixture.full mini_debuginfo
+strip --strip-all --keep-symbol=fixture_static_counter mini_debuginfo
+xz --force mini_debuginfo
+strip --strip-all -o libfixture.minidebug libfixture.full
+objcopy --add-section .gnu_debugdata=mini_debuginfo.xz libfixture.minidebug
+rm mini_debuginfo.xz
+
+# fixture.nopie: non-PIE (ET_EXEC) executable, which is never relocated
+$CC -O0 -no-pie -static -nostdlib -Wl,-e,fixture_function -o fixture.nopie fixture.c
+
+# libfixture.prelinked: shared library with a non-zero preferred load address, like
+# prelinked libraries
+$CC -O0 -shared -fPIC -Wl,-Ttext-segment=0x7fff00000000 -o libfixture.prelinked fixture.c
+
+# libfixture.hugepage: shared library with 2 MiB aligned segments, like older ld defaults
+# and hugepage builds. The code segment is padded to span several pages, and the data
+# segment starts in the page after it.
+echo 'const char fixture_padding[0x2000] = {1};' | $CC -x c -c -fPIC -o padding.o -
+$CC -O0 -shared -fPIC -Wl,-z,noseparate-code,-z,norelro,-z,max-page-size=0x200000 \
+    -o libfixture.hugepage fixture.c padding.o
+strip --strip-all libfixture.hugepage
+rm padding.o
+
+# libfixture.zlib + libfixture.zstd: debug sections compressed with SHF_COMPRESSED
+objcopy --compress-debug-sections=zlib libfixture.full libfixture.zlib
+objcopy --compress-debug-sections=zstd libfixture.full libfixture.zstd
+
+# libstructs.stripped + libstructs.debug: struct layouts in a separate debug file with
+# compressed debug sections. The typedef in structs_decl.c is for a struct that is only
+# declared there, like PyThreadState in most of CPython's compilation units.
+$CC -g -O0 -shared -fPIC -o libstructs.full structs_decl.c structs.c
+objcopy --only-keep-debug --compress-debug-sections=zstd libstructs.full libstructs.debug
+strip --strip-all -o libstructs.stripped libstructs.full
+objcopy --add-gnu-debuglink=libstructs.debug libstructs.stripped
+rm libstructs.full
+
+# libversioned.stripped: GNU symbol versioning, with a default and a hidden version of
+# the same symbol
+$CC -O0 -shared -fPIC -Wl,--version-script=versioned.map -o libversioned.full versioned.c
+strip --strip-all -o libversioned.stripped libversioned.full
+rm libversioned.full
+
+# libusdt.stripped: USDT probes in .note.stapsdt, with and without a semaphore
+$CC -O2 -shared -fPIC -o libusdt.full usdt.c
+strip --strip-all -o libusdt.stripped libusdt.full
+rm libusdt.full
+
+# macho.fat: a fat (universal) Mach-O object with i386, x86_64, arm64 and arm64e slices,
+# which can be built without an Apple toolchain
+LLVM_MC=${LLVM_MC:-llvm-mc}
+LIPO=${LIPO:-llvm-lipo}
+$LLVM_MC -filetype=obj -triple i386-apple-macos10.12 --defsym I386=1 -o macho.i386 macho.s
+$LLVM_MC -filetype=obj -triple x86_64-apple-macos10.15 -o macho.x86_64 macho.s
+$LLVM_MC -filetype=obj -triple arm64-apple-macos11 --defsym ARM64=1 -o macho.arm64 macho.s
+$LLVM_MC -filetype=obj -triple arm64e-apple-macos11 --defsym ARM64=1 --defsym ARM64E=1 \
+    -o macho.arm64e macho.s
+$LIPO -create macho.i386 macho.x86_64 macho.arm64 macho.arm64e -output macho.fat
+rm macho.i386 macho.x86_64 macho.arm64 macho.arm64e
+
+# libfixture32.stripped: a 32 bit (i386) shared library, assembled so that it doesn't need
+# a multilib C toolchain
+$LLVM_MC -filetype=obj -triple i386-linux-gnu -o fixture32.o fixture32.s
+ld -m elf_i386 -shared --build-id=sha1 --hash-style=both -o libfixture32.full fixture32.o
+strip --strip-all -o libfixture32.stripped libfixture32.full
+rm fixture32.o libfixture32.full
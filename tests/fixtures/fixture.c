@@ -0,0 +1,19 @@
+/* Small shared library used by the spytools test suite. See build.sh. */
+
+static int fixture_static_counter = 7;
+
+int fixture_global_data = 42;
+
+/* zero initialized: .bss */
+int fixture_bss_data;
+
+/* thread local storage: .tdata and .tbss, which comes before .bss */
+__thread int fixture_tls_value = 5;
+__thread int fixture_tls_counter;
+
+int fixture_function(int x) {
+    fixture_static_counter += x;
+    fixture_tls_counter += x;
+    fixture_bss_data += fixture_tls_value;
+    return fixture_static_counter + fixture_global_data;
+}
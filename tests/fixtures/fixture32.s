@@ -0,0 +1,56 @@
+# A 32 bit (i386) shared library with code, data, zero filled data, thread local storage
+# and a USDT probe, built without a 32 bit C toolchain. See build.sh.
+    .text
+    .globl fixture_function
+    .type fixture_function, @function
+fixture_function:
+    pushl %ebp
+    movl %esp, %ebp
+990: nop
+    popl %ebp
+    ret
+    .size fixture_function, .-fixture_function
+
+    .data
+    .globl fixture_global_data
+    .type fixture_global_data, @object
+    .align 4
+fixture_global_data:
+    .long 42
+    .size fixture_global_data, 4
+
+    .bss
+    .globl fixture_bss_data
+    .type fixture_bss_data, @object
+    .align 4
+fixture_bss_data:
+    .zero 4
+    .size fixture_bss_data, 4
+
+    .section .tdata, "awT", @progbits
+    .globl fixture_tls_value
+    .type fixture_tls_value, @tls_object
+    .align 4
+fixture_tls_value:
+    .long 5
+    .size fixture_tls_value, 4
+
+    .section .stapsdt.base, "aG", @progbits, .stapsdt.base, comdat
+    .weak _.stapsdt.base
+    .hidden _.stapsdt.base
+_.stapsdt.base:
+    .space 1
+    .size _.stapsdt.base, 1
+
+    .section .note.stapsdt, "?", @note
+    .balign 4
+    .4byte 992f-991f, 994f-993f, 3
+991: .asciz "stapsdt"
+992: .balign 4
+993: .4byte 990b
+    .4byte _.stapsdt.base
+    .4byte 0
+    .asciz "fixture"
+    .asciz "enter"
+    .asciz "-4@8(%ebp)"
+994: .balign 4
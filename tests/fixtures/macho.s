@@ -0,0 +1,40 @@
+# A Mach-O object with a function, initialized data and zero filled data. The symbol
+# names and sizes differ between architectures, so tests can tell which slice was parsed.
+    .text
+    .globl _fixture_function
+_fixture_function:
+.ifdef ARM64
+    .space 32
+.else
+.ifdef I386
+    .space 8
+.else
+    .space 16
+.endif
+.endif
+
+    .data
+    .globl _fixture_global_data
+_fixture_global_data:
+    .long 42
+.ifdef ARM64
+.ifdef ARM64E
+    .globl _fixture_arm64e
+_fixture_arm64e:
+.else
+    .globl _fixture_arm64
+_fixture_arm64:
+.endif
+.else
+.ifdef I386
+    .globl _fixture_i386
+_fixture_i386:
+.else
+    .globl _fixture_x86_64
+_fixture_x86_64:
+.endif
+.endif
+    .long 1
+
+    .zerofill __DATA,__bss,_fixture_bss_data,8,3
+    .globl _fixture_bss_data
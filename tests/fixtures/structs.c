@@ -0,0 +1,30 @@
+/* Structs with a layout to read back from DWARF debug info. See build.sh. */
+
+struct _frame;
+
+typedef struct _ts ThreadState;
+
+struct _ts {
+    struct _ts *prev;
+    struct _ts *next;
+    struct _frame *frame;
+    int recursion_depth;
+    char name[12];
+    union {
+        long as_long;
+        double as_double;
+    };
+    const void *data;
+    unsigned int flags : 3;
+    unsigned int tracing : 1;
+    long thread_id;
+};
+
+typedef struct {
+    ThreadState *tstate_head;
+    int count;
+    short table[4][2];
+} InterpreterState;
+
+ThreadState *structs_current_thread;
+InterpreterState structs_interpreter;
@@ -0,0 +1,5 @@
+/* A compilation unit that only sees a declaration of struct _ts. See build.sh. */
+
+typedef struct _ts ThreadState;
+
+ThreadState *structs_main_thread;
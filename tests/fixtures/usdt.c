@@ -0,0 +1,41 @@
+/* USDT probes in the format of <sys/sdt.h> (which isn't needed to build this), on x86_64 */
+
+#define FIXTURE_PROBE_BASE                                                          \
+    ".ifndef _.stapsdt.base\n"                                                      \
+    ".pushsection .stapsdt.base,\"aG\",\"progbits\",.stapsdt.base,comdat\n"        \
+    ".weak _.stapsdt.base\n"                                                        \
+    ".hidden _.stapsdt.base\n"                                                      \
+    "_.stapsdt.base: .space 1\n"                                                    \
+    ".size _.stapsdt.base, 1\n"                                                     \
+    ".popsection\n"                                                                 \
+    ".endif\n"
+
+#define FIXTURE_PROBE(provider, name, semaphore, args)                              \
+    "990: nop\n"                                                                    \
+    ".pushsection .note.stapsdt,\"?\",\"note\"\n"                                  \
+    ".balign 4\n"                                                                   \
+    ".4byte 992f-991f, 994f-993f, 3\n"                                              \
+    "991: .asciz \"stapsdt\"\n"                                                     \
+    "992: .balign 4\n"                                                              \
+    "993: .8byte 990b\n"                                                            \
+    ".8byte _.stapsdt.base\n"                                                       \
+    ".8byte " semaphore "\n"                                                        \
+    ".asciz \"" provider "\"\n"                                                     \
+    ".asciz \"" name "\"\n"                                                         \
+    ".asciz \"" args "\"\n"                                                         \
+    "994: .balign 4\n"                                                              \
+    ".popsection\n"                                                                 \
+    FIXTURE_PROBE_BASE
+
+unsigned short fixture_tick_semaphore __attribute__((section(".probes"))) = 0;
+
+void usdt_tick(int count, long total) {
+    __asm__ __volatile__(FIXTURE_PROBE("fixture", "tick", "fixture_tick_semaphore",
+                                       "-4@%0 8@%1")
+                         :
+                         : "nor"(count), "nor"(total));
+}
+
+void usdt_plain(void) {
+    __asm__ __volatile__(FIXTURE_PROBE("fixture", "plain", "0", ""));
+}
@@ -0,0 +1,12 @@
+/* Shared library with versioned symbols, like glibc. See build.sh and versioned.map. */
+
+int versioned_v1(void) { return 1; }
+int versioned_v2(void) { return 2; }
+int legacy_v1(void) { return 3; }
+int versioned_data = 4;
+
+/* versioned@VERS_1 is kept for old binaries, new ones link against versioned@@VERS_2 */
+__asm__(".symver versioned_v1, versioned@VERS_1");
+__asm__(".symver versioned_v2, versioned@@VERS_2");
+/* only a hidden version, which dlsym() without a version doesn't return */
+__asm__(".symver legacy_v1, legacy@VERS_1");